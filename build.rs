@@ -0,0 +1,21 @@
+fn main() {
+    #[cfg(feature = "qt5")]
+    qt5::build_scheme_handler();
+}
+
+/// QtWebEngine takes custom scheme handlers only as C++ subclasses, see `src/imp/webview/mod_qt_scheme.cpp`.
+#[cfg(feature = "qt5")]
+mod qt5 {
+    const SOURCE: &str = "src/imp/webview/mod_qt_scheme.cpp";
+
+    pub fn build_scheme_handler() {
+        let qt = pkg_config::Config::new().atleast_version("5.12").probe("Qt5WebEngineWidgets").expect("the qt5 feature needs Qt5WebEngineWidgets and its pkg-config file");
+        let mut build = cc::Build::new();
+        build.cpp(true).file(SOURCE).flag_if_supported("-std=c++17").flag_if_supported("-fPIC");
+        for path in &qt.include_paths {
+            build.include(path);
+        }
+        build.compile("plygui_webview_qt_scheme");
+        println!("cargo:rerun-if-changed={}", SOURCE);
+    }
+}
@@ -0,0 +1,277 @@
+use super::scheme::{SchemeHandler, SchemeRequest, SchemeResponse};
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const MIME_TYPES: &[(&str, &str)] = &[
+    ("html", "text/html"),
+    ("htm", "text/html"),
+    ("js", "text/javascript"),
+    ("mjs", "text/javascript"),
+    ("cjs", "text/javascript"),
+    ("css", "text/css"),
+    ("json", "application/json"),
+    ("map", "application/json"),
+    ("webmanifest", "application/manifest+json"),
+    ("wasm", "application/wasm"),
+    ("txt", "text/plain"),
+    ("md", "text/markdown"),
+    ("csv", "text/csv"),
+    ("xml", "application/xml"),
+    ("svg", "image/svg+xml"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("avif", "image/avif"),
+    ("bmp", "image/bmp"),
+    ("ico", "image/x-icon"),
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+    ("ttf", "font/ttf"),
+    ("otf", "font/otf"),
+    ("eot", "application/vnd.ms-fontobject"),
+    ("mp3", "audio/mpeg"),
+    ("wav", "audio/wav"),
+    ("ogg", "audio/ogg"),
+    ("mp4", "video/mp4"),
+    ("webm", "video/webm"),
+    ("pdf", "application/pdf"),
+    ("zip", "application/zip"),
+];
+
+/// MIME type of a file by its extension, `application/octet-stream` when unknown.
+pub fn mime_type(path: &str) -> &'static str {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    name.rsplit_once('.')
+        .and_then(|(_, extension)| MIME_TYPES.iter().find(|(known, _)| extension.eq_ignore_ascii_case(known)))
+        .map_or("application/octet-stream", |(_, mime_type)| mime_type)
+}
+
+/// Decodes a request path into a relative asset path, e.g. `/img/a%20b.png` into `img/a b.png`.
+/// Returns `None` for paths escaping the root through `..` or holding encoded separators or NULs.
+pub fn normalize_path(path: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut raw = path.bytes();
+    while let Some(byte) = raw.next() {
+        if byte == b'%' {
+            let hex = [raw.next()?, raw.next()?];
+            let decoded = u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?;
+            if decoded == b'/' || decoded == b'\\' {
+                return None;
+            }
+            bytes.push(decoded);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    let decoded = String::from_utf8(bytes).ok()?;
+    let mut segments = Vec::new();
+    for segment in decoded.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => return None,
+            _ if segment.contains('\0') || segment.contains('\\') || segment.contains(':') => return None,
+            _ => segments.push(segment),
+        }
+    }
+    Some(segments.join("/"))
+}
+
+/// Source of the files an `AssetServer` serves, looked up by their normalized relative path.
+pub trait AssetProvider: Send + Sync {
+    fn asset(&self, path: &str) -> Option<Cow<'_, [u8]>>;
+}
+
+impl<F: Fn(&str) -> Option<Vec<u8>> + Send + Sync> AssetProvider for F {
+    fn asset(&self, path: &str) -> Option<Cow<'_, [u8]>> {
+        self(path).map(Cow::Owned)
+    }
+}
+
+/// Files compiled into the binary, see `embed_assets!` and `write_bundle`.
+#[derive(Debug, Clone, Default)]
+pub struct EmbeddedAssets {
+    files: HashMap<&'static str, &'static [u8]>,
+}
+
+impl EmbeddedAssets {
+    pub fn new(files: &[(&'static str, &'static [u8])]) -> Self {
+        EmbeddedAssets { files: files.iter().map(|(path, content)| (path.trim_start_matches('/'), *content)).collect() }
+    }
+    pub fn paths(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.files.keys().copied()
+    }
+}
+
+impl AssetProvider for EmbeddedAssets {
+    fn asset(&self, path: &str) -> Option<Cow<'_, [u8]>> {
+        self.files.get(path).map(|content| Cow::Borrowed(*content))
+    }
+}
+
+/// Files read from a directory on every request, for development.
+#[derive(Debug, Clone)]
+pub struct DirectoryAssets {
+    root: PathBuf,
+}
+
+impl DirectoryAssets {
+    pub fn new<P: AsRef<Path>>(root: P) -> io::Result<Self> {
+        let root = fs::canonicalize(root)?;
+        if !root.is_dir() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "not a directory"));
+        }
+        Ok(DirectoryAssets { root })
+    }
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+}
+
+impl AssetProvider for DirectoryAssets {
+    fn asset(&self, path: &str) -> Option<Cow<'_, [u8]>> {
+        // `path` is already normalized; resolving it again keeps symlinks from leading out of the root.
+        let file = fs::canonicalize(self.root.join(path)).ok()?;
+        if !file.starts_with(&self.root) || !file.is_file() {
+            return None;
+        }
+        fs::read(file).ok().map(Cow::Owned)
+    }
+}
+
+/// Embeds the listed files, relative to `root`, which is itself relative to the invoking source file.
+#[macro_export]
+macro_rules! embed_assets {
+    ($root:literal, [$($file:literal),* $(,)?]) => {
+        $crate::api::assets::EmbeddedAssets::new(&[$(($file, include_bytes!(concat!($root, "/", $file)) as &'static [u8])),*])
+    };
+}
+
+/// Writes to `out` an expression building an `EmbeddedAssets` with every file under `dir`, for use from a build script:
+/// `write_bundle("ui/dist", Path::new(&env::var("OUT_DIR")?).join("ui.rs"))` and then
+/// `let assets = include!(concat!(env!("OUT_DIR"), "/ui.rs"));`.
+pub fn write_bundle<P: AsRef<Path>, Q: AsRef<Path>>(dir: P, out: Q) -> io::Result<()> {
+    let dir = fs::canonicalize(dir)?;
+    let mut files = Vec::new();
+    collect_files(&dir, &dir, &mut files)?;
+    files.sort();
+    let mut code = String::from("plygui_webview::api::assets::EmbeddedAssets::new(&[\n");
+    for (path, file) in &files {
+        let _ = writeln!(code, "    ({:?}, include_bytes!({:?}) as &'static [u8]),", path, file);
+        println!("cargo:rerun-if-changed={}", file);
+    }
+    code.push_str("])\n");
+    println!("cargo:rerun-if-changed={}", dir.display());
+    fs::write(out, code)
+}
+
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<(String, String)>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(root, &path, files)?;
+        } else {
+            let relative = path.strip_prefix(root).map_err(io::Error::other)?;
+            let relative = relative.components().map(|component| component.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+            files.push((relative, path.to_string_lossy().into_owned()));
+        }
+    }
+    Ok(())
+}
+
+/// Answers scheme requests from an `AssetProvider`.
+///
+/// Directories resolve to their `index.html`. Paths without an extension that match no file get the root index
+/// instead of a 404 when the SPA fallback is on, so client-side routes survive a reload.
+#[derive(Clone)]
+pub struct AssetServer {
+    provider: Arc<dyn AssetProvider>,
+    index: String,
+    spa_fallback: bool,
+}
+
+impl AssetServer {
+    pub fn new<P: AssetProvider + 'static>(provider: P) -> Self {
+        AssetServer { provider: Arc::new(provider), index: "index.html".into(), spa_fallback: true }
+    }
+    pub fn with_index<S: Into<String>>(mut self, index: S) -> Self {
+        self.index = index.into();
+        self
+    }
+    pub fn with_spa_fallback(mut self, spa_fallback: bool) -> Self {
+        self.spa_fallback = spa_fallback;
+        self
+    }
+    pub fn provider(&self) -> &Arc<dyn AssetProvider> {
+        &self.provider
+    }
+    pub fn respond(&self, request: &SchemeRequest) -> SchemeResponse {
+        let head = request.method.eq_ignore_ascii_case("HEAD");
+        if !head && !request.method.eq_ignore_ascii_case("GET") {
+            return SchemeResponse::new(405, "text/plain", "Method Not Allowed").with_header("Allow", "GET, HEAD");
+        }
+        let path = match normalize_path(&request.path) {
+            Some(path) => path,
+            None => return SchemeResponse::not_found(),
+        };
+        let found = self.lookup(&path).or_else(|| {
+            let last = path.rsplit('/').next().unwrap_or("");
+            if self.spa_fallback && !last.contains('.') {
+                self.lookup(&self.index)
+            } else {
+                None
+            }
+        });
+        match found {
+            Some((path, content)) => SchemeResponse::ok(mime_type(&path), if head { Vec::new() } else { content.into_owned() }),
+            None => SchemeResponse::not_found(),
+        }
+    }
+    pub fn handler(&self) -> SchemeHandler {
+        let server = self.clone();
+        Arc::new(move |request: &SchemeRequest| server.respond(request))
+    }
+    fn lookup(&self, path: &str) -> Option<(String, Cow<'_, [u8]>)> {
+        if !path.is_empty() {
+            if let Some(content) = self.provider.asset(path) {
+                return Some((path.into(), content));
+            }
+        }
+        let index = if path.is_empty() { self.index.clone() } else { format!("{}/{}", path, self.index) };
+        self.provider.asset(&index).map(|content| (index, content))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_rejects_traversal() {
+        assert_eq!(normalize_path("/img/a%20b.png").as_deref(), Some("img/a b.png"));
+        assert_eq!(normalize_path("/./docs//index.html").as_deref(), Some("docs/index.html"));
+        assert_eq!(normalize_path("/").as_deref(), Some(""));
+        assert_eq!(normalize_path("/../etc/passwd"), None);
+        assert_eq!(normalize_path("/a/%2e%2e/b"), None);
+        assert_eq!(normalize_path("/a%2fb"), None);
+        assert_eq!(normalize_path("/..%5csecret"), None);
+        assert_eq!(normalize_path("/c:/windows"), None);
+        assert_eq!(normalize_path("/a%00"), None);
+        assert_eq!(normalize_path("/a%2"), None);
+    }
+
+    #[test]
+    fn mime_types() {
+        assert_eq!(mime_type("index.html"), "text/html");
+        assert_eq!(mime_type("js/app.mjs"), "text/javascript");
+        assert_eq!(mime_type("X.PNG"), "image/png");
+        assert_eq!(mime_type("v1.0/noext"), "application/octet-stream");
+    }
+}
@@ -0,0 +1,686 @@
+use super::calls::{self, Calls, PendingCall, Reported};
+use super::middleware::{BindCall, BindMiddleware};
+use super::origin::{self, Caller, OriginPattern};
+use super::registry::{self, BindingInfo, BindingRecord};
+use super::rpc::RPC_BINDING;
+use super::typed::{self, BindError, IntoBindError, STATUS_RESOLVED};
+use super::typescript::{self, TsSignature};
+use super::webview::WebviewError;
+
+use serde::Serialize;
+use webview_sys;
+
+use std::ffi::{c_void, CStr, CString};
+use std::os::raw::c_char;
+use std::fmt::{self, Debug};
+use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::any::{Any, TypeId};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, PoisonError, RwLock, Weak};
+use std::thread::{self, ThreadId};
+use std::time::{Duration, Instant};
+
+pub const RUNTIME: &str = include_str!("runtime.js");
+
+/// Panic caught while a binding handled a call from JS, see `set_panic_hook`.
+#[derive(Debug)]
+pub struct BindPanic<'a> {
+    pub binding: &'a str,
+    pub id: &'a str,
+    pub error: &'a BindError,
+}
+
+type PanicHook = Box<dyn Fn(&BindPanic) + Send + Sync>;
+
+lazy_static! {
+    static ref PANIC_HOOK: RwLock<Option<PanicHook>> = RwLock::new(None);
+}
+
+/// Installs the hook notified about every panic caught in a binding. The JS call is rejected either way.
+pub fn set_panic_hook<F: Fn(&BindPanic) + Send + Sync + 'static>(hook: F) {
+    *PANIC_HOOK.write().unwrap_or_else(PoisonError::into_inner) = Some(Box::new(hook));
+}
+pub fn clear_panic_hook() {
+    *PANIC_HOOK.write().unwrap_or_else(PoisonError::into_inner) = None;
+}
+
+/// Shared body of the backends' `extern "C"` bind trampolines, which must neither unwind nor trust the strings they get.
+pub(crate) unsafe fn dispatch<F: FnOnce(&str, &str) -> Result<(), BindError>>(bridge: &WebviewBridge, name: &str, id: *const c_char, req: *const c_char, callback: F) {
+    if id.is_null() {
+        return;
+    }
+    let c_id = CStr::from_ptr(id);
+    let req = if req.is_null() { Ok("[]") } else { CStr::from_ptr(req).to_str() };
+    let (id, req) = match (c_id.to_str(), req) {
+        (Ok(id), Ok(req)) => (id, req),
+        _ => {
+            let (status, result) = typed::rejection(BindError::invalid_arguments("request is not a valid UTF-8 string"));
+            let _ = bridge.return_c(c_id, status, &result);
+            return;
+        }
+    };
+    let (req, reported) = calls::split_caller(req);
+    let res = panic::catch_unwind(AssertUnwindSafe(|| {
+        if let Err(error) = bridge.begin(name, id, &req, reported).and_then(|_| callback(id, &req)) {
+            let (status, result) = typed::rejection(error);
+            let _ = bridge.return_(id, status, &result);
+        }
+    }));
+    if let Err(payload) = res {
+        let error = BindError::panic(&*payload);
+        if let Ok(hook) = PANIC_HOOK.read() {
+            if let Some(ref hook) = *hook {
+                let _ = panic::catch_unwind(AssertUnwindSafe(|| hook(&BindPanic { binding: name, id, error: &error })));
+            }
+        }
+        let (status, result) = typed::rejection(error);
+        let _ = bridge.return_(id, status, &result);
+    }
+}
+
+pub(crate) fn emit_script<P: Serialize + ?Sized>(name: &str, payload: &P) -> Result<String, WebviewError> {
+    let name = typed::to_js_json(name).map_err(|_| WebviewError::InvalidArgument)?;
+    let payload = typed::to_js_value(payload).map_err(|_| WebviewError::InvalidArgument)?;
+    Ok(format!("window.plygui && window.plygui.__emit({}, {});", name, payload))
+}
+
+type ResetHook = Arc<dyn Fn(Option<&str>) + Send + Sync>;
+
+/// Work posted to the UI thread, run only if the bridge is still attached to the webview it was posted to.
+struct Job {
+    bridge: Weak<BridgeInner>,
+    generation: u64,
+    run: Box<dyn FnOnce(webview_sys::webview_t) + Send>,
+}
+
+unsafe extern "C" fn trampoline_job(w: webview_sys::webview_t, arg: *mut c_void) {
+    let job = Box::from_raw(arg as *mut Job);
+    if let Some(inner) = job.bridge.upgrade() {
+        let native = inner.native.read().unwrap_or_else(PoisonError::into_inner);
+        if native.webview == w as usize && native.generation == job.generation {
+            let run = job.run;
+            let _ = panic::catch_unwind(AssertUnwindSafe(move || run(w)));
+        }
+    }
+}
+
+/// Native webview the bridge talks to. It is only read-locked around FFI calls, so `detach` waits for those in flight.
+struct Native {
+    webview: usize,
+    generation: u64,
+}
+
+/// Cheap cloneable handle to the native webview, shared between a control and the objects answering its calls.
+#[derive(Clone)]
+pub struct WebviewBridge {
+    inner: Arc<BridgeInner>,
+}
+
+struct BridgeInner {
+    native: RwLock<Native>,
+    ui_thread: ThreadId,
+    runtime: AtomicBool,
+    bindings: Mutex<BTreeMap<String, BindingRecord>>,
+    rpc_methods: Mutex<BTreeMap<String, TsSignature>>,
+    extensions: Mutex<HashMap<TypeId, Arc<dyn Any + Send + Sync>>>,
+    middleware: RwLock<Vec<Arc<dyn BindMiddleware>>>,
+    calls: Mutex<Calls>,
+    default_origins: RwLock<Option<Vec<OriginPattern>>>,
+    origins: RwLock<HashMap<String, Vec<OriginPattern>>>,
+    default_timeout: RwLock<Option<Duration>>,
+    timeouts: RwLock<HashMap<String, Duration>>,
+    watchdog: AtomicBool,
+    resets: Mutex<Vec<ResetHook>>,
+}
+
+impl WebviewBridge {
+    pub(crate) fn new(webview: webview_sys::webview_t) -> Self {
+        WebviewBridge {
+            inner: Arc::new(BridgeInner {
+                native: RwLock::new(Native { webview: webview as usize, generation: 0 }),
+                ui_thread: thread::current().id(),
+                runtime: AtomicBool::new(false),
+                bindings: Mutex::new(BTreeMap::new()),
+                rpc_methods: Mutex::new(BTreeMap::new()),
+                extensions: Mutex::new(HashMap::new()),
+                middleware: RwLock::new(Vec::new()),
+                calls: Mutex::new(Calls::default()),
+                default_origins: RwLock::new(None),
+                origins: RwLock::new(HashMap::new()),
+                default_timeout: RwLock::new(None),
+                timeouts: RwLock::new(HashMap::new()),
+                watchdog: AtomicBool::new(false),
+                resets: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+    pub(crate) fn attach(&self, webview: webview_sys::webview_t) {
+        let mut native = self.inner.native.write().unwrap_or_else(PoisonError::into_inner);
+        native.webview = webview as usize;
+        native.generation += 1;
+    }
+    /// Cuts the bridge off the native webview, waiting for the FFI calls other threads are making on it.
+    /// Backends call it before the native webview is destroyed.
+    pub(crate) fn detach(&self) {
+        {
+            let mut native = self.inner.native.write().unwrap_or_else(PoisonError::into_inner);
+            native.webview = 0;
+            native.generation += 1;
+        }
+        self.abandon_calls(None);
+    }
+    /// Whether both handles belong to the same webview.
+    pub fn same(&self, other: &WebviewBridge) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+    pub fn is_attached(&self) -> bool {
+        self.inner.native.read().unwrap_or_else(PoisonError::into_inner).webview != 0
+    }
+    /// URL of the page currently loaded, read from the native webview.
+    pub fn url(&self) -> Option<String> {
+        self.with_webview(|webview| unsafe {
+            let c_url = webview_sys::webview_get_url(webview);
+            if c_url.is_null() {
+                None
+            } else {
+                Some(CStr::from_ptr(c_url).to_string_lossy().into_owned())
+            }
+        })
+        .ok()
+        .flatten()
+    }
+    /// Runs `f` with the native webview, which cannot be destroyed meanwhile.
+    fn with_webview<R, F: FnOnce(webview_sys::webview_t) -> R>(&self, f: F) -> Result<R, WebviewError> {
+        let native = self.inner.native.read().unwrap_or_else(PoisonError::into_inner);
+        if native.webview == 0 {
+            return Err(WebviewError::InvalidState);
+        }
+        Ok(f(native.webview as webview_sys::webview_t))
+    }
+    /// Runs `run` on the UI thread, unless the webview is detached before it gets there.
+    fn post<F: FnOnce(webview_sys::webview_t) + Send + 'static>(&self, run: F) -> Result<(), WebviewError> {
+        let native = self.inner.native.read().unwrap_or_else(PoisonError::into_inner);
+        if native.webview == 0 {
+            return Err(WebviewError::InvalidState);
+        }
+        let job = Box::into_raw(Box::new(Job { bridge: Arc::downgrade(&self.inner), generation: native.generation, run: Box::new(run) }));
+        unsafe {
+            let err_code = webview_sys::webview_dispatch(native.webview as webview_sys::webview_t, Some(trampoline_job), job as *mut c_void);
+            let res = WebviewError::from_native(err_code);
+            if res.is_err() {
+                let _ = Box::from_raw(job);
+            }
+            res
+        }
+    }
+    pub(crate) fn is_runtime_installed(&self) -> bool {
+        self.inner.runtime.load(Ordering::SeqCst)
+    }
+    pub(crate) fn mark_runtime_installed(&self) {
+        self.inner.runtime.store(true, Ordering::SeqCst)
+    }
+    /// Evaluates `js` on the UI thread; unlike `Webview::eval` this may be called from any thread.
+    pub fn eval(&self, js: &str) -> Result<(), WebviewError> {
+        let c_js = CString::new(js).map_err(|_| WebviewError::InvalidArgument)?;
+        self.post(move |webview| unsafe {
+            webview_sys::webview_eval(webview, c_js.as_ptr());
+        })
+    }
+    pub fn emit<P: Serialize + ?Sized>(&self, name: &str, payload: &P) -> Result<(), WebviewError> {
+        self.eval(&emit_script(name, payload)?)
+    }
+    /// Per-webview state of the layers built on top of `bind`. Returns whether the state was just created.
+    pub(crate) fn extension<T: Any + Send + Sync, F: FnOnce() -> T>(&self, init: F) -> (Arc<T>, bool) {
+        let mut extensions = self.inner.extensions.lock().unwrap();
+        match extensions.get(&TypeId::of::<T>()).cloned() {
+            Some(extension) => (extension.downcast::<T>().unwrap(), false),
+            None => {
+                let extension = Arc::new(init());
+                extensions.insert(TypeId::of::<T>(), extension.clone());
+                (extension, true)
+            }
+        }
+    }
+    pub(crate) fn get_extension<T: Any + Send + Sync>(&self) -> Option<Arc<T>> {
+        self.inner.extensions.lock().unwrap().get(&TypeId::of::<T>()).cloned().map(|extension| extension.downcast::<T>().unwrap())
+    }
+    pub(crate) fn remove_extension<T: Any + Send + Sync>(&self) {
+        self.inner.extensions.lock().unwrap().remove(&TypeId::of::<T>());
+    }
+    /// Drops the state of every layer, e.g. stopping a loopback server. Backends call it when the control is destroyed.
+    pub(crate) fn release_extensions(&self) {
+        let extensions = mem::take(&mut *self.inner.extensions.lock().unwrap());
+        drop(extensions);
+        self.inner.resets.lock().unwrap().clear();
+    }
+    /// Runs `hook` whenever the page is reset (`None`) or a binding is forgotten (`Some(name)`), so that layers drop their per-page state.
+    pub(crate) fn on_reset<F: Fn(Option<&str>) + Send + Sync + 'static>(&self, hook: F) {
+        self.inner.resets.lock().unwrap().push(Arc::new(hook));
+    }
+    fn reset(&self, binding: Option<&str>) {
+        let hooks = self.inner.resets.lock().unwrap().clone();
+        for hook in hooks {
+            hook(binding);
+        }
+    }
+    /// Records a binding the backend just registered, replacing the record of a previous binding of the same name.
+    pub(crate) fn register(&self, name: &str) {
+        self.inner.bindings.lock().unwrap().insert(name.into(), BindingRecord::new());
+    }
+    pub(crate) fn describe(&self, name: &str, signature: TsSignature) {
+        self.inner.bindings.lock().unwrap().entry(name.into()).or_insert_with(BindingRecord::new).signature = Some(signature);
+    }
+    pub(crate) fn describe_rpc(&self, methods: BTreeMap<String, TsSignature>) {
+        *self.inner.rpc_methods.lock().unwrap() = methods;
+    }
+    pub fn is_bound(&self, name: &str) -> bool {
+        self.inner.bindings.lock().unwrap().contains_key(name)
+    }
+    pub fn binding(&self, name: &str) -> Option<BindingInfo> {
+        let pending = self.inner.calls.lock().unwrap().pending.values().filter(|call| call.binding == name).count();
+        self.inner.bindings.lock().unwrap().get(name).map(|record| record.info(name, pending))
+    }
+    /// Every binding currently registered on the webview, sorted by name.
+    pub fn bindings(&self) -> Vec<BindingInfo> {
+        let mut pending = HashMap::<String, usize>::new();
+        for call in self.inner.calls.lock().unwrap().pending.values() {
+            *pending.entry(call.binding.clone()).or_insert(0) += 1;
+        }
+        self.inner.bindings.lock().unwrap().iter().map(|(name, record)| record.info(name, pending.get(name).cloned().unwrap_or(0))).collect()
+    }
+    /// Drops what the bridge knows about an unbound binding, rejecting its pending calls.
+    pub(crate) fn forget(&self, name: &str) {
+        self.inner.bindings.lock().unwrap().remove(name);
+        if name == RPC_BINDING {
+            self.inner.rpc_methods.lock().unwrap().clear();
+        }
+        let ids = self.inner.calls.lock().unwrap().pending.iter().filter(|(_, call)| call.binding == name).map(|(id, _)| id.clone()).collect::<Vec<_>>();
+        for id in ids {
+            let (status, result) = typed::rejection(BindError::new("AbortError", format!("Binding '{}' was removed", name)).with_code("UNBOUND"));
+            let _ = self.return_(&id, status, &result);
+        }
+        self.reset(Some(name));
+    }
+    /// Renders a `.d.ts` module describing every binding registered through the typed binding layer or described with `describe_binding`.
+    pub fn typescript_declarations(&self) -> String {
+        let bindings = self.inner.bindings.lock().unwrap();
+        typescript::declarations(bindings.iter().filter_map(|(name, record)| record.signature.as_ref().map(|signature| (name, signature))), &self.inner.rpc_methods.lock().unwrap())
+    }
+    pub fn write_typescript_declarations<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.typescript_declarations())
+    }
+    /// Appends `middleware` to the chain run around every call made through this webview's bindings.
+    pub fn add_middleware<M: BindMiddleware + 'static>(&self, middleware: M) {
+        self.inner.middleware.write().unwrap().push(Arc::new(middleware));
+    }
+    pub fn clear_middleware(&self) {
+        self.inner.middleware.write().unwrap().clear();
+    }
+    fn middleware(&self) -> Vec<Arc<dyn BindMiddleware>> {
+        self.inner.middleware.read().unwrap().clone()
+    }
+    /// Restricts every binding without an allowlist of its own to the given origin patterns. `None` allows every origin.
+    pub fn set_default_origins<S: AsRef<str>>(&self, patterns: Option<&[S]>) -> Result<(), WebviewError> {
+        let patterns = patterns.map(origin::parse_patterns).transpose()?;
+        *self.inner.default_origins.write().unwrap() = patterns;
+        Ok(())
+    }
+    /// Restricts the binding `name` to the given origin patterns, overriding the default allowlist. `None` removes the override.
+    pub fn set_binding_origins<S: AsRef<str>>(&self, name: &str, patterns: Option<&[S]>) -> Result<(), WebviewError> {
+        match patterns {
+            Some(patterns) => {
+                let patterns = origin::parse_patterns(patterns)?;
+                self.inner.origins.write().unwrap().insert(name.into(), patterns);
+            }
+            None => {
+                self.inner.origins.write().unwrap().remove(name);
+            }
+        }
+        Ok(())
+    }
+    /// Whether an allowlist applies to `binding`.
+    pub fn is_origin_restricted(&self, binding: &str) -> bool {
+        self.inner.origins.read().unwrap().contains_key(binding) || self.inner.default_origins.read().unwrap().is_some()
+    }
+    pub fn is_origin_allowed(&self, binding: &str, origin: &str) -> bool {
+        if let Some(patterns) = self.inner.origins.read().unwrap().get(binding) {
+            return patterns.iter().any(|pattern| pattern.matches(origin));
+        }
+        match *self.inner.default_origins.read().unwrap() {
+            Some(ref patterns) => patterns.iter().any(|pattern| pattern.matches(origin)),
+            None => true,
+        }
+    }
+    /// Rejects calls to bindings without a timeout of their own once they are pending for longer than `timeout`.
+    pub fn set_default_timeout(&self, timeout: Option<Duration>) {
+        *self.inner.default_timeout.write().unwrap() = timeout;
+        if timeout.is_some() {
+            self.spawn_watchdog();
+        }
+    }
+    pub fn set_binding_timeout(&self, name: &str, timeout: Option<Duration>) {
+        match timeout {
+            Some(timeout) => {
+                self.inner.timeouts.write().unwrap().insert(name.into(), timeout);
+                self.spawn_watchdog();
+            }
+            None => {
+                self.inner.timeouts.write().unwrap().remove(name);
+            }
+        }
+    }
+    fn timeout(&self, binding: &str) -> Option<Duration> {
+        self.inner.timeouts.read().unwrap().get(binding).cloned().or(*self.inner.default_timeout.read().unwrap())
+    }
+    fn spawn_watchdog(&self) {
+        if self.inner.watchdog.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let inner = Arc::downgrade(&self.inner);
+        let spawned = thread::Builder::new().name("plygui-webview-watchdog".into()).spawn(move || watchdog(inner));
+        if spawned.is_err() {
+            self.inner.watchdog.store(false, Ordering::SeqCst);
+        }
+    }
+    /// Rejects the calls past their deadline and returns how long the watchdog may sleep.
+    fn expire_calls(&self) -> Duration {
+        let now = Instant::now();
+        let (expired, next) = {
+            let calls = self.inner.calls.lock().unwrap();
+            let expired = calls.pending.iter().filter(|(_, call)| call.deadline.map(|deadline| deadline <= now).unwrap_or(false)).map(|(id, call)| (id.clone(), call.binding.clone())).collect::<Vec<_>>();
+            let next = calls.pending.values().filter_map(|call| call.deadline).filter(|deadline| *deadline > now).min();
+            (expired, next)
+        };
+        for (id, binding) in expired {
+            let (status, result) = typed::rejection(BindError::new("TimeoutError", format!("Call to '{}' timed out", binding)).with_code("TIMEOUT"));
+            let _ = self.return_(&id, status, &result);
+        }
+        next.map(|next| next - now).unwrap_or(calls::WATCHDOG_IDLE).min(calls::WATCHDOG_IDLE)
+    }
+    /// Page that made the still unanswered call `id`.
+    pub fn caller(&self, id: &str) -> Option<Caller> {
+        self.inner.calls.lock().unwrap().pending.get(id).map(|call| call.caller.clone())
+    }
+    pub fn is_pending(&self, id: &str) -> bool {
+        self.inner.calls.lock().unwrap().pending.contains_key(id)
+    }
+    /// Ids of the calls made from JS that are not answered yet.
+    pub fn pending_calls(&self) -> Vec<String> {
+        self.inner.calls.lock().unwrap().pending.keys().cloned().collect()
+    }
+    /// Forgets every pending call but `keep` without answering it, for when the page that made them is gone.
+    pub(crate) fn abandon_calls(&self, keep: Option<&str>) {
+        {
+            let mut calls = self.inner.calls.lock().unwrap();
+            calls.pending.retain(|id, _| Some(id.as_str()) == keep);
+        }
+        self.reset(None);
+    }
+    /// Aborts the call the runtime tagged with `tag`. Tags are random, so only the frame that made the call can abort it.
+    pub(crate) fn cancel_tagged(&self, tag: &str) {
+        let id = self.inner.calls.lock().unwrap().pending.iter().find(|(_, call)| call.tag.as_deref() == Some(tag)).map(|(id, _)| id.clone());
+        if let Some(id) = id {
+            let (status, result) = typed::rejection(BindError::new("AbortError", "The call was aborted").with_code("ABORTED"));
+            let _ = self.return_(&id, status, &result);
+        }
+    }
+    /// Registers an incoming call, checks its origin and runs the `before` chain over it.
+    ///
+    /// `reported.url` is the calling frame's URL as the runtime reported it. Calls without one are attributed to the page
+    /// loaded. Bindings with an allowlist also refuse URLs whose origin is not the page's, which catches calls still
+    /// arriving from a page that was navigated away from. Page script supplies that URL, so a hostile frame that can
+    /// reach the binding can claim any origin; keep untrusted content out of the webview rather than relying on this.
+    fn begin(&self, binding: &str, id: &str, req: &str, reported: Reported) -> Result<(), BindError> {
+        if let Some(record) = self.inner.bindings.lock().unwrap().get_mut(binding) {
+            record.calls += 1;
+        }
+        let page = Caller::new(self.url().unwrap_or_default());
+        let caller = reported.url.map(Caller::new).unwrap_or_else(|| page.clone());
+        let allowed = calls::is_unrestricted(binding)
+            || (self.is_origin_allowed(binding, &caller.origin) && (caller.origin == page.origin || !self.is_origin_restricted(binding)));
+        let deadline = self.timeout(binding).map(|timeout| Instant::now() + timeout);
+        {
+            let mut calls = self.inner.calls.lock().unwrap();
+            let tag = if registry::is_internal(binding) { None } else { reported.tag };
+            calls.pending.insert(id.into(), PendingCall { binding: binding.into(), req: req.into(), caller: caller.clone(), deadline, tag });
+        }
+        if !allowed {
+            return Err(BindError::new("SecurityError", format!("Origin '{}' is not allowed to call '{}'", caller.origin, binding)).with_code("ORIGIN_NOT_ALLOWED"));
+        }
+        if calls::is_unrestricted(binding) {
+            return Ok(());
+        }
+        let call = BindCall { binding, id, req, url: &caller.url, origin: &caller.origin };
+        self.middleware().iter().try_for_each(|middleware| middleware.before(&call))
+    }
+    /// Answers the call `id`. Calls are answered once, later answers fail with `NotFound`.
+    pub fn return_(&self, id: &str, status: i32, result: &str) -> Result<(), WebviewError> {
+        let c_id = CString::new(id).map_err(|_| WebviewError::InvalidArgument)?;
+        let call = self.inner.calls.lock().unwrap().pending.remove(id).ok_or(WebviewError::NotFound)?;
+        if status != STATUS_RESOLVED {
+            if let Some(record) = self.inner.bindings.lock().unwrap().get_mut(&call.binding) {
+                record.fail(result);
+            }
+        }
+        if !calls::is_unrestricted(&call.binding) {
+            let bind_call = BindCall { binding: &call.binding, id, req: &call.req, url: &call.caller.url, origin: &call.caller.origin };
+            for middleware in self.middleware().iter().rev() {
+                let _ = panic::catch_unwind(AssertUnwindSafe(|| middleware.after(&bind_call, status, result)));
+            }
+        }
+        self.return_c(&c_id, status, result)
+    }
+    /// Answers on the UI thread right away, and from any other thread (e.g. the watchdog) by posting the answer there.
+    fn return_c(&self, c_id: &CStr, status: i32, result: &str) -> Result<(), WebviewError> {
+        let c_result = CString::new(result).map_err(|_| WebviewError::InvalidArgument)?;
+        if thread::current().id() == self.inner.ui_thread {
+            self.with_webview(|webview| unsafe { WebviewError::from_native(webview_sys::webview_return(webview, c_id.as_ptr(), status, c_result.as_ptr())) })?
+        } else {
+            let c_id = c_id.to_owned();
+            self.post(move |webview| unsafe {
+                webview_sys::webview_return(webview, c_id.as_ptr(), status, c_result.as_ptr());
+            })
+        }
+    }
+}
+
+fn watchdog(inner: Weak<BridgeInner>) {
+    loop {
+        let wait = match inner.upgrade() {
+            Some(inner) => WebviewBridge { inner }.expire_calls(),
+            None => return,
+        };
+        thread::sleep(wait);
+    }
+}
+
+impl Debug for WebviewBridge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebviewBridge").field("attached", &self.is_attached()).finish()
+    }
+}
+
+/// Pending JS call that must be answered exactly once. Dropping it unanswered rejects the JS promise.
+pub struct Responder {
+    id: String,
+    caller: Option<Caller>,
+    bridge: WebviewBridge,
+    answered: bool,
+}
+
+impl Responder {
+    pub fn new(bridge: WebviewBridge, id: &str) -> Self {
+        Responder { id: id.into(), caller: bridge.caller(id), bridge, answered: false }
+    }
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+    pub fn caller(&self) -> Option<&Caller> {
+        self.caller.as_ref()
+    }
+    /// Whether the call was answered on the JS side's behalf, e.g. aborted, timed out or unbound, so the result is no longer awaited.
+    pub fn is_cancelled(&self) -> bool {
+        !self.bridge.is_pending(&self.id)
+    }
+    pub fn resolve<R: Serialize>(self, value: R) -> Result<(), WebviewError> {
+        match typed::to_js_value(&value) {
+            Ok(json) => self.respond(STATUS_RESOLVED, &json),
+            Err(e) => self.reject(BindError::invalid_result(e)),
+        }
+    }
+    pub fn reject<E: IntoBindError>(self, error: E) -> Result<(), WebviewError> {
+        let (status, result) = typed::rejection(error);
+        self.respond(status, &result)
+    }
+    pub fn respond(mut self, status: i32, result: &str) -> Result<(), WebviewError> {
+        self.answered = true;
+        self.bridge.return_(&self.id, status, result)
+    }
+}
+
+impl Drop for Responder {
+    fn drop(&mut self) {
+        if !self.answered {
+            let error = if thread::panicking() {
+                BindError::new("Panic", "Binding handler panicked before responding").with_code("PANIC")
+            } else {
+                BindError::new("Error", "Call was dropped without a response").with_code("DROPPED")
+            };
+            let (status, result) = typed::rejection(error);
+            let _ = self.bridge.return_(&self.id, status, &result);
+        }
+    }
+}
+
+impl Debug for Responder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Responder").field("id", &self.id).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::typed::STATUS_REJECTED;
+
+    use serde_json::{json, Value};
+
+    use std::ptr;
+
+    type Answers = Arc<Mutex<Vec<(String, i32, String)>>>;
+
+    struct Recorder(Answers);
+
+    impl BindMiddleware for Recorder {
+        fn after(&self, call: &BindCall, status: i32, result: &str) {
+            self.0.lock().unwrap().push((call.id.into(), status, result.into()));
+        }
+    }
+
+    /// Bridge without a native webview, along with every answer its calls get.
+    fn bridge() -> (WebviewBridge, Answers) {
+        let bridge = WebviewBridge::new(ptr::null_mut());
+        let answers = Answers::default();
+        bridge.add_middleware(Recorder(answers.clone()));
+        (bridge, answers)
+    }
+
+    #[test]
+    fn dropped_responder_rejects() {
+        let (bridge, answers) = bridge();
+        bridge.begin("bound", "1", "[]", Reported::default()).unwrap();
+        drop(Responder::new(bridge.clone(), "1"));
+        let answers = answers.lock().unwrap();
+        assert_eq!(answers.len(), 1);
+        assert_eq!(answers[0].1, STATUS_REJECTED);
+        assert!(answers[0].2.contains("DROPPED"));
+        assert!(!bridge.is_pending("1"));
+    }
+
+    #[test]
+    fn responder_answers_once() {
+        let (bridge, answers) = bridge();
+        bridge.begin("bound", "1", "[]", Reported::default()).unwrap();
+        let _ = Responder::new(bridge.clone(), "1").resolve(1);
+        assert!(matches!(bridge.return_("1", STATUS_RESOLVED, "2"), Err(WebviewError::NotFound)));
+
+        bridge.begin("bound", "2", "[]", Reported::default()).unwrap();
+        let responder = Responder::new(bridge.clone(), "2");
+        let _ = bridge.return_("2", STATUS_RESOLVED, "3");
+        assert!(responder.is_cancelled());
+        assert!(matches!(responder.resolve(4), Err(WebviewError::NotFound)));
+
+        let answers = answers.lock().unwrap();
+        assert_eq!(answers.iter().map(|(id, status, result)| (id.as_str(), *status, result.as_str())).collect::<Vec<_>>(), [("1", STATUS_RESOLVED, "1"), ("2", STATUS_RESOLVED, "3")]);
+    }
+
+    #[test]
+    fn responder_resolves_from_another_thread() {
+        let (bridge, answers) = bridge();
+        bridge.begin("bound", "1", "[]", Reported::default()).unwrap();
+        let responder = Responder::new(bridge.clone(), "1");
+        thread::spawn(move || {
+            let _ = responder.resolve("done");
+        })
+        .join()
+        .unwrap();
+        assert_eq!(*answers.lock().unwrap(), [("1".to_string(), STATUS_RESOLVED, "\"done\"".to_string())]);
+        assert!(!bridge.is_pending("1"));
+    }
+
+    /// Arguments of the `__emit` call `script` consists of, failing unless it is exactly one such call.
+    fn emitted(script: &str) -> (Value, Value) {
+        let args = script.strip_prefix("window.plygui && window.plygui.__emit(").and_then(|script| script.strip_suffix(");")).unwrap();
+        serde_json::from_str(&format!("[{}]", args)).unwrap()
+    }
+
+    #[test]
+    fn emit_escapes_names_and_payloads() {
+        let nasty = "\"double\" 'single' </script><script>alert(1)</script> \u{2028}\u{2029} back\\slash\\\" ); window.x = (";
+        let script = emit_script(nasty, &json!({ nasty: [nasty] })).unwrap();
+        assert!(!script.contains(['\u{2028}', '\u{2029}']));
+        assert_eq!(emitted(&script), (json!(nasty), json!({ nasty: [nasty] })));
+        assert_eq!(emitted(&emit_script("ready", &()).unwrap()), (json!("ready"), Value::Null));
+        assert_eq!(emitted(&emit_script("</script>", "\u{2028}").unwrap()), (json!("</script>"), json!("\u{2028}")));
+    }
+
+    #[test]
+    fn overdue_calls_are_rejected() {
+        let (bridge, answers) = bridge();
+        bridge.set_binding_timeout("slow", Some(Duration::from_millis(0)));
+        bridge.begin("slow", "1", "[]", Reported::default()).unwrap();
+        bridge.begin("other", "2", "[]", Reported::default()).unwrap();
+        bridge.expire_calls();
+        assert!(!bridge.is_pending("1"));
+        assert!(bridge.is_pending("2"));
+        let answers = answers.lock().unwrap();
+        assert_eq!(answers.len(), 1);
+        assert_eq!((answers[0].0.as_str(), answers[0].1), ("1", STATUS_REJECTED));
+        assert!(answers[0].2.contains("TIMEOUT"));
+    }
+
+    #[test]
+    fn bindings_count_calls_and_failures() {
+        let (bridge, _) = bridge();
+        bridge.register("bound");
+        bridge.begin("bound", "1", "[]", Reported::default()).unwrap();
+        assert_eq!(bridge.binding("bound").map(|info| (info.calls, info.pending)), Some((1, 1)));
+        let _ = bridge.return_("1", STATUS_RESOLVED, "null");
+        bridge.begin("bound", "2", "[]", Reported::default()).unwrap();
+        let (status, result) = typed::rejection(BindError::new("RangeError", "too big"));
+        let _ = bridge.return_("2", status, &result);
+        let info = bridge.binding("bound").unwrap();
+        assert_eq!((info.calls, info.failures, info.pending), (2, 1, 0));
+        assert_eq!(info.last_error, Some(BindError::new("RangeError", "too big")));
+        bridge.begin("bound", "3", "[]", Reported::default()).unwrap();
+        let _ = bridge.return_("3", STATUS_RESOLVED, "null");
+        let info = bridge.binding("bound").unwrap();
+        assert_eq!((info.calls, info.failures), (3, 1));
+        assert_eq!(info.last_error, Some(BindError::new("RangeError", "too big")));
+    }
+}
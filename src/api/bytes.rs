@@ -0,0 +1,195 @@
+use super::typescript::TsType;
+
+use serde::de::{self, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{SerializeMap, Serializer};
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+/// Key of the object binary data travels in. The runtime turns such objects into `Uint8Array` and typed arrays into them.
+pub const BYTES_KEY: &str = "__plygui_bytes";
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn encode_base64(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Decodes standard base64, with or without padding.
+pub fn decode_base64(encoded: &str) -> Option<Vec<u8>> {
+    let encoded = encoded.trim_end_matches('=').as_bytes();
+    let mut out = Vec::with_capacity(encoded.len() * 3 / 4);
+    let mut acc = 0u32;
+    let mut bits = 0;
+    for &c in encoded {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return None,
+        };
+        acc = acc << 6 | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    if bits >= 6 {
+        return None;
+    }
+    Some(out)
+}
+
+/// Binary payload crossing the bridge. JS receives it as a `Uint8Array` and may pass `ArrayBuffer`s and typed arrays back to typed bindings or through `plygui.call`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Bytes(pub Vec<u8>);
+
+impl Bytes {
+    pub fn into_inner(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+impl Deref for Bytes {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        &self.0
+    }
+}
+
+impl DerefMut for Bytes {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.0
+    }
+}
+
+impl From<Vec<u8>> for Bytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        Bytes(bytes)
+    }
+}
+
+impl<'a> From<&'a [u8]> for Bytes {
+    fn from(bytes: &'a [u8]) -> Self {
+        Bytes(bytes.to_vec())
+    }
+}
+
+impl From<Bytes> for Vec<u8> {
+    fn from(bytes: Bytes) -> Self {
+        bytes.0
+    }
+}
+
+impl Serialize for Bytes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize(&self.0, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Bytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize(deserializer).map(Bytes)
+    }
+}
+
+impl TsType for Bytes {
+    fn ts_type() -> String {
+        "Uint8Array".into()
+    }
+}
+
+/// Serde adapter sending a `Vec<u8>` field like `Bytes`: `#[serde(with = "plygui_webview::api::bytes")]`.
+pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    let mut map = serializer.serialize_map(Some(1))?;
+    map.serialize_entry(BYTES_KEY, &encode_base64(bytes))?;
+    map.end()
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    deserializer.deserialize_any(BytesVisitor)
+}
+
+struct BytesVisitor;
+
+impl<'de> Visitor<'de> for BytesVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a Uint8Array, an ArrayBuffer, a base64 string or an array of bytes")
+    }
+    fn visit_str<E: de::Error>(self, encoded: &str) -> Result<Vec<u8>, E> {
+        decode_base64(encoded).ok_or_else(|| E::custom("invalid base64"))
+    }
+    fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<Vec<u8>, E> {
+        Ok(bytes.to_vec())
+    }
+    fn visit_byte_buf<E: de::Error>(self, bytes: Vec<u8>) -> Result<Vec<u8>, E> {
+        Ok(bytes)
+    }
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(byte) = seq.next_element::<u8>()? {
+            bytes.push(byte);
+        }
+        Ok(bytes)
+    }
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Vec<u8>, A::Error> {
+        let mut bytes = None;
+        while let Some(key) = map.next_key::<String>()? {
+            if key == BYTES_KEY {
+                let encoded = map.next_value::<String>()?;
+                bytes = Some(decode_base64(&encoded).ok_or_else(|| de::Error::custom("invalid base64"))?);
+            } else {
+                map.next_value::<de::IgnoredAny>()?;
+            }
+        }
+        bytes.ok_or_else(|| de::Error::missing_field(BYTES_KEY))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::typed;
+
+    #[test]
+    fn base64_round_trip() {
+        for len in 0..40usize {
+            let bytes = (0..len).map(|i| (i * 37 + 11) as u8).collect::<Vec<_>>();
+            let encoded = encode_base64(&bytes);
+            assert_eq!(decode_base64(&encoded).unwrap(), bytes);
+            assert_eq!(decode_base64(encoded.trim_end_matches('=')).unwrap(), bytes);
+        }
+        assert_eq!(encode_base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(encode_base64(b"fo"), "Zm8=");
+        assert!(decode_base64("Z").is_none());
+    }
+
+    #[test]
+    fn bytes_cross_the_bridge() {
+        let bytes = Bytes(vec![1, 2, 255]);
+        let (back, _): (Bytes, u8) = typed::parse_args(&format!("[{}, 5]", serde_json::to_string(&bytes).unwrap())).unwrap();
+        assert_eq!(back, bytes);
+        let back: Bytes = serde_json::from_str("[1,2,255]").unwrap();
+        assert_eq!(back, bytes);
+    }
+}
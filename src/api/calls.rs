@@ -0,0 +1,145 @@
+use super::eval::EVAL_BINDING;
+use super::origin::Caller;
+use super::stream::STREAM_CANCEL_BINDING;
+use super::typed::{self, STATUS_RESOLVED};
+use super::webview::{WebviewError, WebviewExt};
+
+use serde_json::Value;
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+pub const CANCEL_BINDING: &str = "__plygui_cancel";
+pub const PAGE_BINDING: &str = "__plygui_page";
+/// Key of the object the runtime appends to the arguments of its calls, carrying the calling frame's URL.
+pub const CALLER_KEY: &str = "__plygui_caller";
+/// Key of the random tag the runtime adds to that object for calls made with an `AbortSignal`.
+pub const TAG_KEY: &str = "__plygui_tag";
+
+pub(crate) const WATCHDOG_IDLE: Duration = Duration::from_millis(100);
+
+/// Calls made from JS that still await their answer.
+#[derive(Default)]
+pub(crate) struct Calls {
+    pub pending: HashMap<String, PendingCall>,
+}
+
+pub(crate) struct PendingCall {
+    pub binding: String,
+    pub req: String,
+    pub caller: Caller,
+    pub deadline: Option<Instant>,
+    pub tag: Option<String>,
+}
+
+/// What the runtime reported along with a call. Page script supplies it, so none of it is authenticated.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Reported {
+    pub url: Option<String>,
+    pub tag: Option<String>,
+}
+
+/// Internal bindings exempt from origin allowlists, since rejecting them would leave e.g. `eval_async` futures pending
+/// forever. Each of them acts only on a random token handed to the page for that purpose (an evaluation, an abortable
+/// call, a stream, the runtime of a new document), so a frame that never received the token cannot use them.
+pub(crate) fn is_unrestricted(binding: &str) -> bool {
+    [PAGE_BINDING, CANCEL_BINDING, EVAL_BINDING, STREAM_CANCEL_BINDING].contains(&binding)
+}
+
+/// 128 bits from the OS random number generator, hex encoded.
+pub(crate) fn new_token() -> Result<String, WebviewError> {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).map_err(|e| WebviewError::Unspecified(e.raw_os_error().unwrap_or(-1)))?;
+    let mut token = String::with_capacity(bytes.len() * 2);
+    for byte in &bytes {
+        let _ = write!(token, "{:02x}", byte);
+    }
+    Ok(token)
+}
+
+/// Splits what the runtime reported off the arguments of a call made through it.
+pub(crate) fn split_caller(req: &str) -> (Cow<str>, Reported) {
+    if !req.contains(CALLER_KEY) {
+        return (Cow::Borrowed(req), Reported::default());
+    }
+    let mut args = match serde_json::from_str::<Value>(req) {
+        Ok(Value::Array(args)) => args,
+        _ => return (Cow::Borrowed(req), Reported::default()),
+    };
+    let reported = match args.last() {
+        Some(Value::Object(caller)) if caller.keys().all(|key| key == CALLER_KEY || key == TAG_KEY) => caller.get(CALLER_KEY).and_then(Value::as_str).map(|url| Reported {
+            url: Some(url.into()),
+            tag: caller.get(TAG_KEY).and_then(Value::as_str).map(String::from),
+        }),
+        _ => None,
+    };
+    match reported {
+        Some(reported) => {
+            args.pop();
+            (Cow::Owned(Value::Array(args).to_string()), reported)
+        }
+        None => (Cow::Borrowed(req), Reported::default()),
+    }
+}
+
+/// Binds the helpers the runtime uses to abort calls and to report that a new document replaced the old one.
+///
+/// Only the script this adds to every new top-level document knows the token `PAGE_BINDING` wants, so other frames
+/// cannot drop the calls of the page.
+pub(crate) fn install<W: WebviewExt>(webview: &mut W) -> Result<(), WebviewError> {
+    let context = Arc::new(RwLock::new(()));
+    webview.bind(Cow::Borrowed(CANCEL_BINDING), context.clone(), |this: &mut W, id, req, _| {
+        let bridge = this.bridge();
+        let _ = bridge.return_(id, STATUS_RESOLVED, "null");
+        if let Ok((tag,)) = typed::parse_args::<(String,)>(req) {
+            bridge.cancel_tagged(&tag);
+        }
+    })?;
+    let token = new_token()?;
+    let page = format!(
+        "window === window.top && document.readyState === 'loading' && typeof window.{0} === 'function' && window.{0}({1});",
+        PAGE_BINDING,
+        typed::to_js_json(&token).map_err(|_| WebviewError::InvalidArgument)?
+    );
+    webview.bind(Cow::Borrowed(PAGE_BINDING), context, move |this: &mut W, id, req, _| {
+        let bridge = this.bridge();
+        if typed::parse_args::<(String,)>(req).is_ok_and(|(reported,)| reported == token) {
+            bridge.abandon_calls(Some(id));
+        }
+        let _ = bridge.return_(id, STATUS_RESOLVED, "null");
+    })?;
+    webview.init(Cow::Owned(page))
+}
+
+pub(crate) fn uninstall<W: WebviewExt>(webview: &mut W) {
+    for name in &[CANCEL_BINDING, PAGE_BINDING] {
+        if webview.is_bound(name) {
+            let _ = webview.unbind(Cow::Borrowed(name));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn caller_is_split_off() {
+        let (req, reported) = split_caller(r#"[1,"a",{"__plygui_caller":"https://app.test/page"}]"#);
+        assert_eq!(req, r#"[1,"a"]"#);
+        assert_eq!(reported, Reported { url: Some("https://app.test/page".into()), tag: None });
+        let (req, reported) = split_caller(r#"[{"__plygui_caller":"https://app.test/","__plygui_tag":"f00d"}]"#);
+        assert_eq!(req, "[]");
+        assert_eq!(reported, Reported { url: Some("https://app.test/".into()), tag: Some("f00d".into()) });
+    }
+
+    #[test]
+    fn args_without_caller_are_kept() {
+        for req in &["[]", "[1,2]", r#"[{"__plygui_caller":1}]"#, r#"[{"__plygui_caller":"https://app.test/","other":1}]"#, r#"[{"__plygui_caller":"https://app.test/"},1]"#, "not json __plygui_caller"] {
+            assert_eq!(split_caller(req), (Cow::Borrowed(*req), Reported::default()));
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use super::typed::BindError;
+
+use std::cell::RefCell;
+use std::sync::{PoisonError, RwLock};
+
+thread_local! {
+    static ENTERED: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+struct Entered(usize);
+
+impl Drop for Entered {
+    fn drop(&mut self) {
+        ENTERED.with(|entered| {
+            let mut entered = entered.borrow_mut();
+            if let Some(position) = entered.iter().rposition(|context| *context == self.0) {
+                entered.remove(position);
+            }
+        });
+    }
+}
+
+/// Locks a binding context for the duration of `f`, as the trampolines do for every call.
+///
+/// A handler that spins a nested event loop (e.g. a modal dialog) may see another call arrive for a binding sharing
+/// its context while the context is still locked further up the same thread's stack. Such a call is rejected with
+/// a `REENTRANT` error instead of deadlocking; bindings made through `WebviewExt::bind_fn` never share a context.
+/// Locks held by other threads are waited for as usual.
+pub fn enter<C, R, F: FnOnce(&mut C) -> R>(context: &RwLock<C>, f: F) -> Result<R, BindError> {
+    let key = context as *const RwLock<C> as usize;
+    if ENTERED.with(|entered| entered.borrow().contains(&key)) {
+        return Err(BindError::new("ReentrancyError", "The binding context is already in use by a call on this thread").with_code("REENTRANT"));
+    }
+    ENTERED.with(|entered| entered.borrow_mut().push(key));
+    let _entered = Entered(key);
+    let mut context = context.write().unwrap_or_else(PoisonError::into_inner);
+    Ok(f(&mut context))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_call_on_the_same_context_is_rejected() {
+        let shared = RwLock::new(1);
+        let other = RwLock::new(2);
+        let (nested, unrelated) = enter(&shared, |value| {
+            *value += 1;
+            (enter(&shared, |_| ()).map_err(|e| e.code), enter(&other, |value| *value))
+        })
+        .unwrap();
+        assert_eq!(nested, Err(Some("REENTRANT".into())));
+        assert_eq!(unrelated.unwrap(), 2);
+        assert_eq!(enter(&shared, |value| *value).unwrap(), 2);
+    }
+
+    #[test]
+    fn panicking_call_releases_the_context() {
+        let shared = RwLock::new(());
+        let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| enter(&shared, |_| panic!("handler"))));
+        assert!(enter(&shared, |_| ()).is_ok());
+    }
+}
@@ -0,0 +1,99 @@
+use super::bridge::WebviewBridge;
+use super::bytes;
+use super::loopback;
+use super::webview::WebviewError;
+
+use std::str;
+
+/// Longest URL the engines load, Chromium's limit. `set_html` of WebView2 and Qt is bound by it as well.
+pub const MAX_URL_LENGTH: usize = 2 * 1024 * 1024;
+
+/// What a backend without a native way to load bytes navigates to for `load_data`.
+pub(crate) enum DataSource {
+    Html(String),
+    Url(String),
+}
+
+/// Picks how `load_data` shows `data` on backends that only take URLs and HTML strings.
+///
+/// A `base_url` is honoured for UTF-8 HTML only, through an injected `<base>`; other documents yield `InvalidArgument`.
+/// Documents too large for `MAX_URL_LENGTH` are served from a loopback server instead.
+pub(crate) fn data_source(bridge: &WebviewBridge, data: &[u8], mime_type: &str, encoding: Option<&str>, base_url: Option<&str>) -> Result<DataSource, WebviewError> {
+    if let Some(base_url) = base_url {
+        let html = mime_type.split(';').next().is_some_and(|mime_type| mime_type.trim().eq_ignore_ascii_case("text/html"));
+        let utf8 = encoding.is_none_or(|encoding| encoding.trim().eq_ignore_ascii_case("utf-8"));
+        let text = str::from_utf8(data).ok().filter(|text| html && utf8 && !text.contains('\0')).ok_or(WebviewError::InvalidArgument)?;
+        let html = inject_base(text, base_url);
+        if html.len() <= MAX_URL_LENGTH {
+            return Ok(DataSource::Html(html));
+        }
+        return loopback::serve_document(bridge, html.into_bytes(), "text/html; charset=utf-8".into()).map(DataSource::Url);
+    }
+    let url = data_url(data, mime_type, encoding);
+    if url.len() <= MAX_URL_LENGTH {
+        return Ok(DataSource::Url(url));
+    }
+    loopback::serve_document(bridge, data.to_vec(), content_type(mime_type, encoding)).map(DataSource::Url)
+}
+
+/// Makes `html` resolve relative URLs against `base_url` by inserting a `<base>` element at the start of its head,
+/// for backends whose native `set_html` takes no base URL.
+pub fn inject_base(html: &str, base_url: &str) -> String {
+    let base = format!("<base href=\"{}\">", escape_attribute(base_url));
+    let lower = html.to_ascii_lowercase();
+    let head = lower.match_indices("<head").map(|(start, _)| start).find(|&start| {
+        lower[start + 5..].chars().next().is_some_and(|c| c == '>' || c.is_ascii_whitespace())
+    });
+    if let Some(at) = head.and_then(|start| lower[start..].find('>').map(|end| start + end + 1)) {
+        return format!("{}{}{}", &html[..at], base, &html[at..]);
+    }
+    // Without a head, one goes right after the doctype so that the document does not fall into quirks mode.
+    let end = if lower.trim_start().starts_with("<!doctype") { lower.find('>').map_or(0, |end| end + 1) } else { 0 };
+    format!("{}<head>{}</head>{}", &html[..end], base, &html[end..])
+}
+
+/// `data:` URL holding `data`, for backends that cannot load bytes directly.
+pub fn data_url(data: &[u8], mime_type: &str, encoding: Option<&str>) -> String {
+    format!("data:{};base64,{}", content_type(mime_type, encoding), bytes::encode_base64(data))
+}
+
+fn content_type(mime_type: &str, encoding: Option<&str>) -> String {
+    let mime_type = mime_type.trim();
+    let mime_type = if mime_type.is_empty() { "application/octet-stream" } else { mime_type };
+    match encoding.map(str::trim).filter(|encoding| !encoding.is_empty()) {
+        Some(encoding) => format!("{};charset={}", mime_type, encoding),
+        None => mime_type.into(),
+    }
+}
+
+fn escape_attribute(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base_goes_into_the_head() {
+        assert_eq!(inject_base("<html><HEAD lang=x><title>t</title></head></html>", "app://x/\""), "<html><HEAD lang=x><base href=\"app://x/&quot;\"><title>t</title></head></html>");
+        assert_eq!(inject_base("<!doctype html><p>x", "b"), "<!doctype html><head><base href=\"b\"></head><p>x");
+        assert_eq!(inject_base("<header>x</header>", "b"), "<head><base href=\"b\"></head><header>x</header>");
+    }
+
+    #[test]
+    fn data_urls() {
+        assert_eq!(data_url(b"hi", "text/plain", Some("utf-8")), "data:text/plain;charset=utf-8;base64,aGk=");
+        assert_eq!(data_url(&[0xff], "", None), "data:application/octet-stream;base64,/w==");
+    }
+}
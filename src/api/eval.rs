@@ -0,0 +1,212 @@
+use super::calls;
+use super::typed::{self, STATUS_RESOLVED};
+use super::webview::{WebviewError, WebviewExt};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::future::Future;
+use std::mem;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, RwLock};
+use std::task::{Context, Poll, Waker};
+
+pub const EVAL_BINDING: &str = "__plygui_eval";
+/// Sent by every new document, so that evaluations the previous one never answered are rejected.
+const PAGE_TOKEN: u64 = 0;
+
+/// Exception thrown by the page while evaluating a script.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JsError {
+    pub name: String,
+    pub message: String,
+    #[serde(default)]
+    pub stack: Option<String>,
+}
+
+impl JsError {
+    pub fn new<N: Into<String>, M: Into<String>>(name: N, message: M) -> Self {
+        JsError { name: name.into(), message: message.into(), stack: None }
+    }
+}
+
+impl Display for JsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.name, self.message)
+    }
+}
+
+impl std::error::Error for JsError {}
+
+type Callback<W> = Box<dyn FnOnce(&mut W, Result<Value, JsError>) + Send>;
+
+/// Evaluations awaiting their result, by the random token that lets only the evaluated script deliver it.
+struct Evaluations<W> {
+    pending: HashMap<String, Callback<W>>,
+    aborted: Vec<Callback<W>>,
+}
+
+impl<W> Evaluations<W> {
+    fn abort(&mut self) {
+        let pending = mem::take(&mut self.pending);
+        self.aborted.extend(pending.into_values());
+    }
+}
+
+/// Rejects the evaluations of a page that went away. Callbacks need the webview, so this runs on its next use.
+fn reject_aborted<W>(this: &mut W, evaluations: &Mutex<Evaluations<W>>) {
+    let aborted = mem::take(&mut evaluations.lock().unwrap().aborted);
+    for callback in aborted {
+        callback(this, Err(JsError::new("AbortError", "The page was replaced before the script returned")));
+    }
+}
+
+fn install<W: WebviewExt + 'static>(webview: &mut W) -> Result<Arc<Mutex<Evaluations<W>>>, WebviewError> {
+    webview.install_runtime()?;
+    let bridge = webview.bridge();
+    let (evaluations, created) = bridge.extension(|| Mutex::new(Evaluations::<W> { pending: HashMap::new(), aborted: Vec::new() }));
+    if created {
+        let registry = evaluations.clone();
+        let res = webview.bind(Cow::Borrowed(EVAL_BINDING), Arc::new(RwLock::new(())), move |this: &mut W, id, req, _| {
+            let _ = this.return_(Cow::Borrowed(id), STATUS_RESOLVED, Cow::Borrowed("null"));
+            reject_aborted(this, &registry);
+            if let Ok((Value::String(token), ok, value)) = typed::parse_args::<(Value, bool, Value)>(req) {
+                let callback = registry.lock().unwrap().pending.remove(&token);
+                if let Some(callback) = callback {
+                    let result = if ok {
+                        Ok(value)
+                    } else {
+                        Err(serde_json::from_value(value.clone()).unwrap_or_else(|_| JsError::new("Error", value.to_string())))
+                    };
+                    callback(this, result);
+                }
+            }
+        });
+        let ping = format!("window.{0} && window.{0}({1}, true, null);", EVAL_BINDING, PAGE_TOKEN);
+        let res = res.and_then(|_| webview.init(Cow::Owned(ping)));
+        if let Err(e) = res {
+            bridge.remove_extension::<Mutex<Evaluations<W>>>();
+            return Err(e);
+        }
+        let registry = Arc::downgrade(&evaluations);
+        bridge.on_reset(move |binding| {
+            if binding.is_none_or(|binding| binding == EVAL_BINDING) {
+                if let Some(registry) = registry.upgrade() {
+                    registry.lock().unwrap().abort();
+                }
+            }
+        });
+    }
+    Ok(evaluations)
+}
+
+/// Evaluates `js` through `eval`, so that statements yield their completion value.
+pub(crate) fn eval_with_result<W, F>(webview: &mut W, js: &str, callback: F) -> Result<(), WebviewError>
+where
+    W: WebviewExt + 'static,
+    F: FnOnce(&mut W, Result<Value, JsError>) + Send + 'static,
+{
+    let expression = format!("(0, eval)({})", typed::to_js_json(js).map_err(|_| WebviewError::InvalidArgument)?);
+    evaluate(webview, &expression, callback)
+}
+
+/// Evaluates the JS expression `expression` as part of the dispatched script, which needs no `'unsafe-eval'`.
+pub(crate) fn evaluate<W, F>(webview: &mut W, expression: &str, callback: F) -> Result<(), WebviewError>
+where
+    W: WebviewExt + 'static,
+    F: FnOnce(&mut W, Result<Value, JsError>) + Send + 'static,
+{
+    let evaluations = install(webview)?;
+    reject_aborted(webview, &evaluations);
+    let token = calls::new_token()?;
+    let script = format!(
+        "window.plygui.__evalResult({}, {}, function () {{ return {}; }});",
+        typed::to_js_json(EVAL_BINDING).map_err(|_| WebviewError::InvalidArgument)?,
+        typed::to_js_json(&token).map_err(|_| WebviewError::InvalidArgument)?,
+        expression
+    );
+    evaluations.lock().unwrap().pending.insert(token.clone(), Box::new(callback));
+    webview.eval(Cow::Owned(script)).inspect_err(|_| {
+        evaluations.lock().unwrap().pending.remove(&token);
+    })
+}
+
+/// Builds a script invoking the function at `path` (e.g. `app.getState`) with `args` spread as its arguments.
+pub fn call_script<A: Serialize>(path: &str, args: &A) -> Result<String, WebviewError> {
+    if path.is_empty() || path.split('.').any(|part| part.is_empty()) {
+        return Err(WebviewError::InvalidArgument);
+    }
+    let args = match serde_json::to_value(args).map_err(|_| WebviewError::InvalidArgument)? {
+        Value::Null => Value::Array(Vec::new()),
+        args @ Value::Array(_) => args,
+        arg => Value::Array(vec![arg]),
+    };
+    Ok(format!(
+        "window.plygui.__call({}, {})",
+        typed::to_js_json(path).map_err(|_| WebviewError::InvalidArgument)?,
+        typed::to_js_value(&args).map_err(|_| WebviewError::InvalidArgument)?
+    ))
+}
+
+struct Shared<T> {
+    result: Option<Result<T, JsError>>,
+    waker: Option<Waker>,
+}
+
+/// Resolves once the page delivers a result, see `WebviewExt::eval_async`.
+pub struct JsFuture<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+}
+
+pub(crate) struct JsCompleter<T> {
+    shared: Option<Arc<Mutex<Shared<T>>>>,
+}
+
+impl<T> JsFuture<T> {
+    pub(crate) fn new() -> (Self, JsCompleter<T>) {
+        let shared = Arc::new(Mutex::new(Shared { result: None, waker: None }));
+        (JsFuture { shared: shared.clone() }, JsCompleter { shared: Some(shared) })
+    }
+}
+
+impl<T> JsCompleter<T> {
+    pub(crate) fn complete(mut self, result: Result<T, JsError>) {
+        self.complete_inner(result)
+    }
+    fn complete_inner(&mut self, result: Result<T, JsError>) {
+        if let Some(shared) = self.shared.take() {
+            let waker = {
+                let mut shared = shared.lock().unwrap();
+                shared.result = Some(result);
+                shared.waker.take()
+            };
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl<T> Drop for JsCompleter<T> {
+    fn drop(&mut self) {
+        self.complete_inner(Err(JsError::new("AbortError", "The page never delivered a result")))
+    }
+}
+
+impl<T> Future for JsFuture<T> {
+    type Output = Result<T, JsError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut shared = self.shared.lock().unwrap();
+        match shared.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use super::assets::AssetServer;
+use super::scheme::{SchemeHandler, SchemeRequest, SchemeResponse};
+use super::webview::{WebviewError, WebviewExt};
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// Scheme the virtual hosts of a webview are served under, e.g. `plygui://app.local/index.html`.
+///
+/// Hosts are not served as `https://`: none of the engines lets an app answer https requests itself, short of
+/// WebView2's folder mapping, which `webview_sys` does not expose. Pages therefore get a `plygui://<host>` origin.
+/// WebKitGTK treats the scheme as secure, so secure-context APIs such as `crypto.subtle` work there;
+/// QtWebEngine only does for schemes the app declared with `QWebEngineUrlScheme` before creating its `QApplication`.
+/// Win32 serves hosts through `serve_host_or_loopback` only, from `http://127.0.0.1`, which browsers also treat as secure.
+pub const HOST_SCHEME: &str = "plygui";
+
+type Hosts = RwLock<HashMap<String, AssetServer>>;
+
+pub fn check_host(host: &str) -> Result<String, WebviewError> {
+    let host = host.to_ascii_lowercase();
+    let valid = !host.is_empty() && host.split('.').all(|label| !label.is_empty() && !label.starts_with('-') && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'));
+    if valid {
+        Ok(host)
+    } else {
+        Err(WebviewError::InvalidArgument)
+    }
+}
+
+/// Serves `server` as `host` and returns the base URL of the host.
+pub(crate) fn serve<W: WebviewExt>(webview: &mut W, host: &str, server: AssetServer) -> Result<String, WebviewError> {
+    let host = check_host(host)?;
+    let bridge = webview.bridge();
+    let (hosts, created) = bridge.extension::<Hosts, _>(|| RwLock::new(HashMap::new()));
+    if created {
+        let registry = hosts.clone();
+        let handler: SchemeHandler = Arc::new(move |request: &SchemeRequest| {
+            let server = registry.read().unwrap().get(&request.host).cloned();
+            server.map_or_else(SchemeResponse::not_found, |server| server.respond(request))
+        });
+        if let Err(e) = webview.register_scheme(Cow::Borrowed(HOST_SCHEME), handler) {
+            bridge.remove_extension::<Hosts>();
+            return Err(e);
+        }
+    }
+    let url = format!("{}://{}/", HOST_SCHEME, host);
+    hosts.write().unwrap().insert(host, server);
+    Ok(url)
+}
+
+pub(crate) fn unserve<W: WebviewExt>(webview: &mut W, host: &str) -> Result<(), WebviewError> {
+    let host = check_host(host)?;
+    let hosts = webview.bridge().get_extension::<Hosts>().ok_or(WebviewError::NotFound)?;
+    let removed = hosts.write().unwrap().remove(&host);
+    removed.map(|_| ()).ok_or(WebviewError::NotFound)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_names() {
+        for (host, expected) in &[("app", "app"), ("App.Local", "app.local"), ("my-app.test", "my-app.test"), ("127.0.0.1", "127.0.0.1"), ("x1.y2", "x1.y2")] {
+            assert_eq!(check_host(host).ok().as_deref(), Some(*expected), "{}", host);
+        }
+        for host in &["", ".", "a..b", ".app", "app.", "-app", "app.-x", "a/b", "a b", "a:80", "user@app", "ãpp", "app_local"] {
+            assert!(check_host(host).is_err(), "{}", host);
+        }
+    }
+}
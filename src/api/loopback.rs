@@ -0,0 +1,322 @@
+use super::assets::AssetServer;
+use super::calls::new_token;
+use super::bridge::WebviewBridge;
+use super::scheme::{SchemeHandler, SchemeRequest, SchemeResponse};
+use super::webview::WebviewError;
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+const TOKEN_COOKIE: &str = "plygui_token";
+const MAX_HEAD: u64 = 64 * 1024;
+const IO_TIMEOUT: Duration = Duration::from_secs(10);
+/// Connections served at once; further ones get a 503 instead of another thread.
+const MAX_CONNECTIONS: usize = 32;
+
+/// HTTP server on 127.0.0.1 for backends without custom schemes, see `WebviewExt::serve_loopback`.
+///
+/// Only requests under `/<token>/`, or carrying the token cookie set by such a request, with a loopback `Host`
+/// header are answered, so other local processes and pages cannot read the assets. Stops when dropped.
+pub struct LoopbackServer {
+    address: SocketAddr,
+    token: String,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl LoopbackServer {
+    pub fn start(server: AssetServer) -> Result<Self, WebviewError> {
+        LoopbackServer::start_with(server.handler())
+    }
+    /// Answers every request carrying the token through `handler`, with the token stripped from the path.
+    pub fn start_with(handler: SchemeHandler) -> Result<Self, WebviewError> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).map_err(io_error)?;
+        let address = listener.local_addr().map_err(io_error)?;
+        let token = new_token()?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let token = token.clone();
+            let stop = stop.clone();
+            let active = Arc::new(AtomicUsize::new(0));
+            thread::Builder::new()
+                .name("plygui-webview-loopback".into())
+                .spawn(move || {
+                    for stream in listener.incoming() {
+                        if stop.load(Ordering::SeqCst) {
+                            break;
+                        }
+                        if let Ok(stream) = stream {
+                            if active.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                                active.fetch_sub(1, Ordering::SeqCst);
+                                let _ = stream.set_write_timeout(Some(IO_TIMEOUT));
+                                let _ = respond(&stream, SchemeResponse::new(503, "text/plain", "Service Unavailable"), None);
+                                continue;
+                            }
+                            let connection = Connection(active.clone());
+                            let handler = handler.clone();
+                            let token = token.clone();
+                            let _ = thread::Builder::new().spawn(move || {
+                                let _connection = connection;
+                                let _ = serve(stream, address, &token, &handler);
+                            });
+                        }
+                    }
+                })
+                .map_err(io_error)?
+        };
+        Ok(LoopbackServer { address, token, stop, thread: Some(thread) })
+    }
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+    /// URL to navigate to, e.g. `http://127.0.0.1:49152/3f9c.../`.
+    pub fn url(&self) -> String {
+        format!("http://{}/{}/", self.address, self.token)
+    }
+}
+
+impl Drop for LoopbackServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        // Wakes up the accept loop so that it sees the flag.
+        let _ = TcpStream::connect_timeout(&self.address, Duration::from_secs(1));
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Counts a connection as served until dropped, including when its thread could not be spawned.
+struct Connection(Arc<AtomicUsize>);
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn io_error(e: io::Error) -> WebviewError {
+    WebviewError::Unspecified(e.raw_os_error().unwrap_or(-1))
+}
+
+/// Server of the document last shown through `load_data`, apart from the one `serve_loopback` starts.
+struct DocumentServer(Mutex<Option<LoopbackServer>>);
+
+/// Serves `body` at the root of a new loopback server for as long as the control shows it, and returns its URL.
+/// For documents too large for a `data:` URL on backends that cannot load bytes directly.
+pub(crate) fn serve_document(bridge: &WebviewBridge, body: Vec<u8>, content_type: String) -> Result<String, WebviewError> {
+    let body = Arc::new(body);
+    let server = LoopbackServer::start_with(Arc::new(move |request: &SchemeRequest| {
+        if request.path != "/" {
+            SchemeResponse::not_found()
+        } else if request.method.eq_ignore_ascii_case("GET") {
+            SchemeResponse::ok(content_type.clone(), body.to_vec())
+        } else {
+            SchemeResponse::new(405, "text/plain", "Method Not Allowed").with_header("Allow", "GET")
+        }
+    }))?;
+    let url = server.url();
+    let (slot, _) = bridge.extension(|| DocumentServer(Mutex::new(None)));
+    let previous = slot.0.lock().unwrap().replace(server);
+    drop(previous);
+    Ok(url)
+}
+
+fn serve(stream: TcpStream, address: SocketAddr, token: &str, handler: &SchemeHandler) -> io::Result<()> {
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    let mut reader = BufReader::new((&stream).take(MAX_HEAD));
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (method.to_string(), target.to_string()),
+        _ => return respond(&stream, SchemeResponse::new(400, "text/plain", "Bad Request"), None),
+    };
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return respond(&stream, SchemeResponse::new(400, "text/plain", "Bad Request"), None);
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    let mut request = SchemeRequest::new(method, format!("http://{}{}", address, target));
+    request.headers = headers;
+
+    let loopback_host = |host: &str| host == address.to_string() || host == format!("localhost:{}", address.port());
+    if !request.header("Host").is_some_and(loopback_host) {
+        return respond(&stream, SchemeResponse::new(403, "text/plain", "Forbidden"), None);
+    }
+    let prefix = format!("/{}", token);
+    let tokened = request.path == prefix || request.path.starts_with(&format!("{}/", prefix));
+    if tokened {
+        request.path = request.path[prefix.len()..].to_string();
+        if request.path.is_empty() {
+            request.path.push('/');
+        }
+    } else if !has_token_cookie(&request, token) {
+        return respond(&stream, SchemeResponse::new(403, "text/plain", "Forbidden"), None);
+    }
+    let cookie = if tokened { Some(format!("{}={}; Path=/; HttpOnly; SameSite=Strict", TOKEN_COOKIE, token)) } else { None };
+    respond(&stream, handler(&request), cookie)
+}
+
+fn has_token_cookie(request: &SchemeRequest, token: &str) -> bool {
+    request.headers.iter().filter(|(name, _)| name.eq_ignore_ascii_case("Cookie")).flat_map(|(_, value)| value.split(';')).any(|cookie| {
+        cookie.trim().split_once('=').is_some_and(|(name, value)| name == TOKEN_COOKIE && value == token)
+    })
+}
+
+fn respond(mut stream: &TcpStream, response: SchemeResponse, cookie: Option<String>) -> io::Result<()> {
+    let mime_type = if response.mime_type.contains(['\r', '\n']) { "application/octet-stream" } else { &response.mime_type };
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\nCache-Control: no-store\r\nX-Content-Type-Options: nosniff\r\n",
+        response.status,
+        reason_phrase(response.status),
+        mime_type,
+        response.body.len()
+    );
+    if let Some(cookie) = cookie {
+        head.push_str(&format!("Set-Cookie: {}\r\n", cookie));
+    }
+    for (name, value) in &response.headers {
+        if !name.contains(['\r', '\n']) && !value.contains(['\r', '\n']) {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(&response.body)?;
+    stream.flush()?;
+    let _ = stream.shutdown(Shutdown::Write);
+    Ok(())
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        206 => "Partial Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        304 => "Not Modified",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        503 => "Service Unavailable",
+        _ => "Unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::assets::EmbeddedAssets;
+    use std::time::Instant;
+
+    fn request(address: SocketAddr, head: &str) -> String {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(head.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    fn get(server: &LoopbackServer, path: &str, headers: &str) -> String {
+        request(server.address(), &format!("GET {} HTTP/1.1\r\nHost: {}\r\n{}\r\n", path, server.address(), headers))
+    }
+
+    fn assets() -> LoopbackServer {
+        LoopbackServer::start(AssetServer::new(EmbeddedAssets::new(&[("index.html", b"<h1>"), ("app.css", b"body {}")]))).unwrap()
+    }
+
+    #[test]
+    fn listens_on_loopback_only() {
+        let server = assets();
+        assert_eq!(server.address().ip(), Ipv4Addr::LOCALHOST);
+        assert_eq!(server.url(), format!("http://127.0.0.1:{}/{}/", server.address().port(), server.token));
+        assert_eq!(server.token.len(), 32);
+        assert_ne!(server.token, assets().token);
+    }
+
+    #[test]
+    fn token_in_the_path_sets_the_cookie() {
+        let server = assets();
+        let response = get(&server, &format!("/{}/", server.token), "");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+        assert!(response.contains(&format!("Set-Cookie: {}={};", TOKEN_COOKIE, server.token)), "{}", response);
+        assert!(response.ends_with("<h1>"), "{}", response);
+    }
+
+    #[test]
+    fn missing_or_wrong_token_is_forbidden() {
+        let server = assets();
+        let wrong = "0123456789abcdef0123456789abcdef";
+        assert!(get(&server, "/app.css", "").starts_with("HTTP/1.1 403"));
+        assert!(get(&server, &format!("/{}/app.css", wrong), "").starts_with("HTTP/1.1 403"));
+        assert!(get(&server, "/app.css", &format!("Cookie: {}={}\r\n", TOKEN_COOKIE, wrong)).starts_with("HTTP/1.1 403"));
+        let response = get(&server, "/app.css", &format!("Cookie: theme=dark; {}={}\r\n", TOKEN_COOKIE, server.token));
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n") && response.ends_with("body {}"), "{}", response);
+    }
+
+    #[test]
+    fn foreign_host_is_forbidden() {
+        let server = assets();
+        let path = format!("/{}/", server.token);
+        for host in &["Host: evil.example\r\n", "Host: 127.0.0.1\r\n", "Host: 127.0.0.1.evil.example\r\n", ""] {
+            let response = request(server.address(), &format!("GET {} HTTP/1.1\r\n{}\r\n", path, host));
+            assert!(response.starts_with("HTTP/1.1 403"), "{:?}: {}", host, response);
+        }
+        let response = request(server.address(), &format!("GET {} HTTP/1.1\r\nHost: localhost:{}\r\n\r\n", path, server.address().port()));
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    }
+
+    #[test]
+    fn content_type_cannot_inject_headers() {
+        let server = LoopbackServer::start_with(Arc::new(|_: &SchemeRequest| SchemeResponse::ok("text/html\r\nX-Injected: 1", "x"))).unwrap();
+        let response = get(&server, &format!("/{}/", server.token), "");
+        assert!(response.contains("Content-Type: application/octet-stream\r\n"), "{}", response);
+        assert!(!response.contains("X-Injected"), "{}", response);
+    }
+
+    #[test]
+    fn connections_beyond_the_cap_are_refused() {
+        let server = assets();
+        let idle = (0..MAX_CONNECTIONS).map(|_| TcpStream::connect(server.address()).unwrap()).collect::<Vec<_>>();
+        let mut refused = TcpStream::connect(server.address()).unwrap();
+        let mut response = String::new();
+        refused.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 503"), "{}", response);
+        drop(idle);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !get(&server, &format!("/{}/", server.token), "").starts_with("HTTP/1.1 200") {
+            assert!(Instant::now() < deadline);
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn stops_when_dropped() {
+        let server = assets();
+        let address = server.address();
+        drop(server);
+        assert!(TcpStream::connect(address).is_err());
+    }
+}
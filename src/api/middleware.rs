@@ -0,0 +1,30 @@
+use super::typed::BindError;
+
+/// Call made from JS through one of the webview's bindings.
+#[derive(Debug, Clone, Copy)]
+pub struct BindCall<'a> {
+    pub binding: &'a str,
+    pub id: &'a str,
+    pub req: &'a str,
+    pub url: &'a str,
+    pub origin: &'a str,
+}
+
+/// Hook run around every call made through `WebviewExt::bind`, see `WebviewBridge::add_middleware`.
+///
+/// The runtime's own plumbing (evaluation results, aborting calls and streams, page changes) bypasses middleware, so
+/// e.g. an authentication check cannot leave `eval_async` futures pending. Stores and JSON-RPC go through it.
+pub trait BindMiddleware: Send + Sync {
+    /// Runs before the binding, in registration order. An error rejects the call without running the binding or the rest of the chain.
+    fn before(&self, _call: &BindCall) -> Result<(), BindError> {
+        Ok(())
+    }
+    /// Runs in reverse registration order once the call is answered, whether by the binding or by a rejecting `before`.
+    fn after(&self, _call: &BindCall, _status: i32, _result: &str) {}
+}
+
+impl<F: Fn(&BindCall) -> Result<(), BindError> + Send + Sync> BindMiddleware for F {
+    fn before(&self, call: &BindCall) -> Result<(), BindError> {
+        self(call)
+    }
+}
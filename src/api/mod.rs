@@ -1 +1,21 @@
 pub mod webview;
+pub mod bridge;
+pub mod bytes;
+pub mod calls;
+pub mod context;
+pub mod middleware;
+pub mod origin;
+pub mod registry;
+pub mod typed;
+pub mod namespace;
+pub mod typescript;
+pub mod eval;
+pub mod rpc;
+pub mod store;
+pub mod stream;
+pub mod pending;
+pub mod scheme;
+pub mod assets;
+pub mod hosts;
+pub mod loopback;
+pub mod document;
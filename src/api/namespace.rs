@@ -0,0 +1,79 @@
+use super::bridge::Responder;
+use super::origin::Caller;
+use super::typed::{self, IntoBindError};
+use super::webview::{WebviewBindContext, WebviewError, WebviewExt};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use std::borrow::Cow;
+use std::sync::{Arc, RwLock};
+
+pub const NAMESPACE_ROOT: &str = "api";
+
+type Method<W, C> = Box<dyn FnOnce(&mut W, Cow<str>, Arc<RwLock<C>>) -> Result<(), WebviewError>>;
+
+/// Group of typed methods exposed to JS as `window.api.<namespace>.<method>()`, all sharing one context.
+pub struct WebviewNamespace<W: WebviewExt, C: WebviewBindContext> {
+    name: String,
+    methods: Vec<(String, Method<W, C>)>,
+}
+
+/// Rust object whose methods are exposed as one namespace via `WebviewExt::bind_api`.
+pub trait WebviewApi<W: WebviewExt>: WebviewBindContext {
+    fn namespace(&self) -> Cow<str>;
+    fn methods(namespace: WebviewNamespace<W, Self>) -> WebviewNamespace<W, Self>;
+}
+
+impl<W: WebviewExt + 'static, C: WebviewBindContext + 'static> WebviewNamespace<W, C> {
+    pub fn new<S: Into<String>>(name: S) -> Self {
+        WebviewNamespace { name: name.into(), methods: Vec::new() }
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn path(&self) -> String {
+        format!("{}.{}", NAMESPACE_ROOT, self.name)
+    }
+    pub fn method<S, A, R, E, F>(mut self, name: S, callback: F) -> Self
+    where
+        S: Into<String>,
+        A: DeserializeOwned,
+        R: Serialize,
+        E: IntoBindError,
+        F: FnMut(&mut W, A, &Caller, &mut C) -> Result<R, E> + 'static,
+    {
+        self.methods.push((name.into(), Box::new(move |webview: &mut W, name: Cow<str>, context| webview.bind_typed(name, context, callback))));
+        self
+    }
+    pub fn deferred<S, A, F>(mut self, name: S, callback: F) -> Self
+    where
+        S: Into<String>,
+        A: DeserializeOwned,
+        F: FnMut(&mut W, A, Responder, &mut C) + 'static,
+    {
+        self.methods.push((name.into(), Box::new(move |webview: &mut W, name: Cow<str>, context| webview.bind_deferred(name, context, callback))));
+        self
+    }
+    pub(crate) fn bind(self, webview: &mut W, context: Arc<RwLock<C>>) -> Result<(), WebviewError> {
+        let path = self.path();
+        let mut names = Vec::with_capacity(self.methods.len());
+        for (name, method) in self.methods {
+            if let Err(e) = method(webview, Cow::Owned(format!("{}.{}", path, name)), context.clone()) {
+                // Leave no half-bound namespace behind.
+                for name in names {
+                    let _ = webview.unbind(Cow::Owned(format!("{}.{}", path, name)));
+                }
+                return Err(e);
+            }
+            names.push(name);
+        }
+        let shim = format!(
+            "window.plygui.__namespace({}, {});",
+            typed::to_js_json(&path).map_err(|_| WebviewError::InvalidArgument)?,
+            typed::to_js_json(&names).map_err(|_| WebviewError::InvalidArgument)?
+        );
+        webview.install_runtime()?;
+        webview.init(Cow::Borrowed(&shim))?;
+        webview.eval(Cow::Owned(shim))
+    }
+}
@@ -0,0 +1,137 @@
+use super::webview::WebviewError;
+
+use std::fmt::{self, Display};
+
+/// Frame a binding call came from, as reported by the runtime running in it. Calls made without the runtime are
+/// attributed to the top-level page. Page script can report any URL, so this is not proof of where a call came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Caller {
+    pub url: String,
+    pub origin: String,
+}
+
+impl Caller {
+    pub fn new<S: Into<String>>(url: S) -> Self {
+        let url = url.into();
+        Caller { origin: origin_of(&url), url }
+    }
+}
+
+/// Serializes the origin of `url` as `scheme://host[:port]`, dropping default ports.
+/// URLs without an authority, such as `about:blank` or `data:`, yield `scheme:`; unparseable ones yield `null`.
+pub fn origin_of(url: &str) -> String {
+    let colon = match url.find(':') {
+        Some(colon) if colon > 0 => colon,
+        _ => return "null".into(),
+    };
+    let scheme = url[..colon].to_ascii_lowercase();
+    if !scheme.chars().all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.') {
+        return "null".into();
+    }
+    let rest = &url[colon + 1..];
+    if !rest.starts_with("//") {
+        return format!("{}:", scheme);
+    }
+    let authority = &rest[2..];
+    let authority = &authority[..authority.find(['/', '?', '#']).unwrap_or(authority.len())];
+    let authority = authority.rsplit('@').next().unwrap_or("").to_ascii_lowercase();
+    let authority = match (scheme.as_str(), authority.rfind(':')) {
+        ("http", Some(port)) | ("ws", Some(port)) if &authority[port..] == ":80" => &authority[..port],
+        ("https", Some(port)) | ("wss", Some(port)) if &authority[port..] == ":443" => &authority[..port],
+        _ => &authority,
+    };
+    format!("{}://{}", scheme, authority)
+}
+
+/// Entry of a binding's origin allowlist.
+///
+/// `*` allows every origin, `scheme:` or `scheme://` every origin of a scheme (e.g. `file://`),
+/// `https://*.example.com` every subdomain of a host, and anything else the exact origin of the given URL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OriginPattern {
+    Any,
+    Scheme(String),
+    Subdomains { scheme: String, suffix: String },
+    Exact(String),
+}
+
+impl OriginPattern {
+    pub fn parse(pattern: &str) -> Result<Self, WebviewError> {
+        let pattern = pattern.trim();
+        if pattern == "*" {
+            return Ok(OriginPattern::Any);
+        }
+        let origin = origin_of(pattern);
+        if origin == "null" {
+            return Err(WebviewError::InvalidArgument);
+        }
+        let scheme = origin[..origin.find(':').unwrap()].to_string();
+        if origin.ends_with(':') || origin.ends_with("://") {
+            return Ok(OriginPattern::Scheme(scheme));
+        }
+        let authority = &origin[scheme.len() + 3..];
+        if authority.starts_with("*.") && authority.len() > 2 {
+            return Ok(OriginPattern::Subdomains { suffix: authority[1..].into(), scheme });
+        }
+        if authority.contains('*') {
+            return Err(WebviewError::InvalidArgument);
+        }
+        Ok(OriginPattern::Exact(origin))
+    }
+    pub fn matches(&self, origin: &str) -> bool {
+        match self {
+            OriginPattern::Any => true,
+            OriginPattern::Scheme(scheme) => origin != "null" && origin.starts_with(scheme.as_str()) && origin[scheme.len()..].starts_with(':'),
+            OriginPattern::Subdomains { scheme, suffix } => {
+                let prefix = format!("{}://", scheme);
+                origin.starts_with(&prefix) && origin.ends_with(suffix.as_str()) && origin.len() > prefix.len() + suffix.len()
+            }
+            OriginPattern::Exact(exact) => exact == origin,
+        }
+    }
+}
+
+impl Display for OriginPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OriginPattern::Any => write!(f, "*"),
+            OriginPattern::Scheme(scheme) => write!(f, "{}:", scheme),
+            OriginPattern::Subdomains { scheme, suffix } => write!(f, "{}://*{}", scheme, suffix),
+            OriginPattern::Exact(origin) => write!(f, "{}", origin),
+        }
+    }
+}
+
+pub(crate) fn parse_patterns<S: AsRef<str>>(patterns: &[S]) -> Result<Vec<OriginPattern>, WebviewError> {
+    patterns.iter().map(|pattern| OriginPattern::parse(pattern.as_ref())).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn origin_of_urls() {
+        assert_eq!(origin_of("HTTPS://user:pw@Example.com:443/a?b#c"), "https://example.com");
+        assert_eq!(origin_of("http://localhost:8080"), "http://localhost:8080");
+        assert_eq!(origin_of("app://bundle/index.html"), "app://bundle");
+        assert_eq!(origin_of("file:///home/index.html"), "file://");
+        assert_eq!(origin_of("about:blank"), "about:");
+        assert_eq!(origin_of(""), "null");
+    }
+
+    #[test]
+    fn patterns() {
+        let pattern = |pattern| OriginPattern::parse(pattern).unwrap();
+        assert!(pattern("https://*.example.com").matches("https://a.b.example.com"));
+        assert!(!pattern("https://*.example.com").matches("https://example.com"));
+        assert!(!pattern("https://*.example.com").matches("https://evilexample.com"));
+        assert!(!pattern("https://example.com").matches("http://example.com"));
+        assert!(pattern("https://example.com/path").matches("https://example.com"));
+        assert!(pattern("app:").matches("app://bundle"));
+        assert!(!pattern("file://").matches("filex://"));
+        assert!(pattern("*").matches("null"));
+        assert!(OriginPattern::parse("https://ex*.com").is_err());
+        assert_eq!(pattern("https://*.example.com").to_string(), "https://*.example.com");
+    }
+}
@@ -0,0 +1,134 @@
+use super::webview::WebviewError;
+
+/// Operation a backend could not apply yet because its native webview does not exist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PendingOperation {
+    Navigate(String),
+    SetHtml(String),
+    Init(String),
+    Eval(String),
+    Bind(String),
+}
+
+impl PendingOperation {
+    /// Init scripts and bindings belong to the control, and the document last loaded is loaded again,
+    /// so they outlive a replay. Evaluations only make sense in the document they were made for.
+    pub fn is_persistent(&self) -> bool {
+        !matches!(self, PendingOperation::Eval(_))
+    }
+    pub fn is_document(&self) -> bool {
+        matches!(self, PendingOperation::Navigate(_) | PendingOperation::SetHtml(_))
+    }
+    /// Rejects what the native side would reject anyway, so queued calls fail as early as immediate ones.
+    pub fn check(&self) -> Result<(), WebviewError> {
+        let text = match self {
+            PendingOperation::Navigate(text) | PendingOperation::SetHtml(text) | PendingOperation::Init(text) | PendingOperation::Eval(text) | PendingOperation::Bind(text) => text,
+        };
+        if text.contains('\0') {
+            Err(WebviewError::InvalidArgument)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Ordered log of operations replayed whenever a backend creates its native webview.
+///
+/// Everything pushed before creation is replayed in order. Afterwards only the persistent operations are kept,
+/// together with those `remember`ed while the webview existed, so that a webview destroyed and created again
+/// (e.g. when the control moves to another container) gets the same init scripts, bindings and document back.
+/// Of the documents only the last one is kept, and it is loaded after the init scripts so that they run in it.
+///
+/// Only backends creating their native webview lazily need it: Win32 creates it once the control is added to a
+/// container, while GTK and Qt create it along with the control and apply every operation right away, in call order.
+#[derive(Debug, Clone, Default)]
+pub struct PendingQueue {
+    operations: Vec<PendingOperation>,
+}
+
+impl PendingQueue {
+    pub fn new() -> Self {
+        PendingQueue::default()
+    }
+    pub fn push(&mut self, operation: PendingOperation) -> Result<(), WebviewError> {
+        operation.check()?;
+        self.operations.push(operation);
+        Ok(())
+    }
+    /// Keeps an operation already applied to the native webview if it has to be applied again to the next one.
+    pub fn remember(&mut self, operation: PendingOperation) {
+        if operation.is_document() {
+            self.operations.retain(|operation| !operation.is_document());
+            self.operations.push(operation);
+        } else if operation.is_persistent() {
+            let document = self.operations.iter().position(PendingOperation::is_document).unwrap_or(self.operations.len());
+            self.operations.insert(document, operation);
+        }
+    }
+    /// Drops a binding so that it is not replayed.
+    pub fn forget_bind(&mut self, name: &str) {
+        self.operations.retain(|operation| match operation {
+            PendingOperation::Bind(bound) => bound != name,
+            _ => true,
+        });
+    }
+    /// Operations to apply to a newly created native webview, in the order they were made.
+    pub fn replay(&mut self) -> Vec<PendingOperation> {
+        let operations = self.operations.clone();
+        let document = self.operations.iter().rposition(PendingOperation::is_document).map(|document| self.operations.remove(document));
+        self.operations.retain(|operation| operation.is_persistent() && !operation.is_document());
+        self.operations.extend(document);
+        operations
+    }
+    /// Last URL queued for navigation, unless HTML was set after it.
+    pub fn url(&self) -> Option<&str> {
+        self.operations
+            .iter()
+            .rev()
+            .filter_map(|operation| match operation {
+                PendingOperation::Navigate(url) => Some(Some(url.as_str())),
+                PendingOperation::SetHtml(_) => Some(None),
+                _ => None,
+            })
+            .next()
+            .flatten()
+    }
+    pub fn len(&self) -> usize {
+        self.operations.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PendingOperation::*;
+    use super::*;
+
+    #[test]
+    fn replay_keeps_persistent_operations() {
+        let mut queue = PendingQueue::new();
+        queue.push(Navigate("app://a/".into())).unwrap();
+        queue.push(Init("init".into())).unwrap();
+        queue.push(Eval("eval".into())).unwrap();
+        queue.push(Bind("bound".into())).unwrap();
+        assert!(queue.push(Eval("nul\0".into())).is_err());
+        assert_eq!(queue.url(), Some("app://a/"));
+        assert_eq!(queue.replay(), vec![Navigate("app://a/".into()), Init("init".into()), Eval("eval".into()), Bind("bound".into())]);
+        assert_eq!(queue.replay(), vec![Init("init".into()), Bind("bound".into()), Navigate("app://a/".into())]);
+        queue.forget_bind("bound");
+        assert_eq!(queue.len(), 2);
+    }
+
+    #[test]
+    fn remember_keeps_the_last_document_last() {
+        let mut queue = PendingQueue::new();
+        queue.remember(Navigate("app://a/".into()));
+        queue.remember(SetHtml("<p>".into()));
+        queue.remember(Init("init".into()));
+        queue.remember(Eval("eval".into()));
+        assert_eq!(queue.url(), None);
+        assert_eq!(queue.replay(), vec![Init("init".into()), SetHtml("<p>".into())]);
+    }
+}
@@ -0,0 +1,76 @@
+use super::typed::{BindError, REJECTION_PREFIX, REJECTION_SUFFIX};
+use super::typescript::TsSignature;
+
+use std::time::SystemTime;
+
+/// Snapshot of a binding as reported by `WebviewBridge::bindings`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BindingInfo {
+    pub name: String,
+    pub registered_at: SystemTime,
+    pub calls: u64,
+    pub failures: u64,
+    pub pending: usize,
+    pub last_error: Option<BindError>,
+    /// Present for bindings made through the typed layer.
+    pub signature: Option<TsSignature>,
+    /// Whether the binding is one of the helpers the runtime relies on.
+    pub internal: bool,
+}
+
+pub(crate) struct BindingRecord {
+    pub registered_at: SystemTime,
+    pub calls: u64,
+    pub failures: u64,
+    pub last_error: Option<BindError>,
+    pub signature: Option<TsSignature>,
+}
+
+impl BindingRecord {
+    pub fn new() -> Self {
+        BindingRecord { registered_at: SystemTime::now(), calls: 0, failures: 0, last_error: None, signature: None }
+    }
+    pub fn info(&self, name: &str, pending: usize) -> BindingInfo {
+        BindingInfo {
+            name: name.into(),
+            registered_at: self.registered_at,
+            calls: self.calls,
+            failures: self.failures,
+            pending,
+            last_error: self.last_error.clone(),
+            signature: self.signature.clone(),
+            internal: is_internal(name),
+        }
+    }
+    /// Records a rejection, recovering the `BindError` from the script built by `typed::rejection` when possible.
+    pub fn fail(&mut self, result: &str) {
+        let error = result
+            .strip_prefix(REJECTION_PREFIX)
+            .and_then(|json| json.strip_suffix(REJECTION_SUFFIX))
+            .unwrap_or(result);
+        self.failures += 1;
+        self.last_error = Some(serde_json::from_str(error).unwrap_or_else(|_| BindError::new("Error", result)));
+    }
+}
+
+pub fn is_internal(name: &str) -> bool {
+    name.starts_with("__plygui_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::typed;
+
+    #[test]
+    fn failures_keep_the_last_error() {
+        let mut record = BindingRecord::new();
+        record.fail(&typed::rejection(BindError::new("RangeError", "too big").with_code("RANGE")).1);
+        record.fail("not a rejection script");
+        let info = record.info("bound", 0);
+        assert_eq!(info.failures, 2);
+        assert_eq!(info.last_error, Some(BindError::new("Error", "not a rejection script")));
+        assert!(!info.internal);
+        assert!(record.info("__plygui_eval", 0).internal);
+    }
+}
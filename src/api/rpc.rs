@@ -0,0 +1,245 @@
+use super::typed::{self, BindError, STATUS_RESOLVED};
+use super::typescript::TsSignature;
+use super::webview::{WebviewError, WebviewExt};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Display};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, RwLock};
+
+pub const RPC_BINDING: &str = "__plygui_jsonrpc";
+
+/// JSON-RPC 2.0 error object.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl RpcError {
+    pub const PARSE_ERROR: i64 = -32700;
+    pub const INVALID_REQUEST: i64 = -32600;
+    pub const METHOD_NOT_FOUND: i64 = -32601;
+    pub const INVALID_PARAMS: i64 = -32602;
+    pub const INTERNAL_ERROR: i64 = -32603;
+    pub const SERVER_ERROR: i64 = -32000;
+
+    pub fn new<M: Into<String>>(code: i64, message: M) -> Self {
+        RpcError { code, message: message.into(), data: None }
+    }
+    pub fn with_data(mut self, data: Value) -> Self {
+        self.data = Some(data);
+        self
+    }
+    pub fn parse_error() -> Self {
+        RpcError::new(Self::PARSE_ERROR, "Parse error")
+    }
+    pub fn invalid_request() -> Self {
+        RpcError::new(Self::INVALID_REQUEST, "Invalid Request")
+    }
+    pub fn method_not_found() -> Self {
+        RpcError::new(Self::METHOD_NOT_FOUND, "Method not found")
+    }
+    pub fn invalid_params<D: Display>(details: D) -> Self {
+        RpcError::new(Self::INVALID_PARAMS, "Invalid params").with_data(Value::String(details.to_string()))
+    }
+    pub fn internal_error<D: Display>(details: D) -> Self {
+        RpcError::new(Self::INTERNAL_ERROR, "Internal error").with_data(Value::String(details.to_string()))
+    }
+}
+
+impl Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.message, self.code)
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+impl From<String> for RpcError {
+    fn from(message: String) -> Self {
+        RpcError::new(Self::SERVER_ERROR, message)
+    }
+}
+
+impl<'a> From<&'a str> for RpcError {
+    fn from(message: &'a str) -> Self {
+        RpcError::new(Self::SERVER_ERROR, message)
+    }
+}
+
+type Handler<W> = Box<dyn FnMut(&mut W, Value) -> Result<Value, RpcError>>;
+
+/// JSON-RPC 2.0 dispatcher, exposed to the page by `WebviewExt::bind_jsonrpc`.
+pub struct JsonRpc<W> {
+    methods: HashMap<String, Handler<W>>,
+    signatures: BTreeMap<String, TsSignature>,
+}
+
+impl<W> Default for JsonRpc<W> {
+    fn default() -> Self {
+        JsonRpc { methods: HashMap::new(), signatures: BTreeMap::new() }
+    }
+}
+
+impl<W> JsonRpc<W> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn method<S, P, R, E, F>(mut self, name: S, mut handler: F) -> Self
+    where
+        S: Into<String>,
+        P: DeserializeOwned,
+        R: Serialize,
+        E: Into<RpcError>,
+        F: FnMut(&mut W, P) -> Result<R, E> + 'static,
+    {
+        let name = name.into();
+        self.signatures.insert(name.clone(), TsSignature::of::<P, R>());
+        self.methods.insert(
+            name,
+            Box::new(move |webview, params| {
+                let params = match params {
+                    Value::Null => typed::args_from_value(Value::Array(Vec::new())),
+                    params => typed::args_from_value(params),
+                }
+                .map_err(RpcError::invalid_params)?;
+                let result = handler(webview, params).map_err(Into::into)?;
+                serde_json::to_value(result).map_err(RpcError::internal_error)
+            }),
+        );
+        self
+    }
+    pub fn has_method(&self, name: &str) -> bool {
+        self.methods.contains_key(name)
+    }
+    /// TypeScript signature of every method, as declared in `PlyguiRpcMethods`.
+    pub fn signatures(&self) -> &BTreeMap<String, TsSignature> {
+        &self.signatures
+    }
+    /// Handles a request, notification or batch, returning the response message if one is due.
+    pub fn handle(&mut self, webview: &mut W, message: &str) -> Option<Value> {
+        match serde_json::from_str(message) {
+            Ok(message) => self.handle_value(webview, message),
+            Err(_) => Some(response(Value::Null, Err(RpcError::parse_error()))),
+        }
+    }
+    pub fn handle_value(&mut self, webview: &mut W, message: Value) -> Option<Value> {
+        match message {
+            Value::Array(batch) => {
+                if batch.is_empty() {
+                    return Some(response(Value::Null, Err(RpcError::invalid_request())));
+                }
+                let responses = batch.into_iter().filter_map(|call| self.call(webview, call)).collect::<Vec<_>>();
+                if responses.is_empty() {
+                    None
+                } else {
+                    Some(Value::Array(responses))
+                }
+            }
+            call => self.call(webview, call),
+        }
+    }
+    fn call(&mut self, webview: &mut W, call: Value) -> Option<Value> {
+        let mut call = match call {
+            Value::Object(call) => call,
+            _ => return Some(response(Value::Null, Err(RpcError::invalid_request()))),
+        };
+        let id = call.remove("id");
+        let valid_id = matches!(id, None | Some(Value::Null) | Some(Value::String(_)) | Some(Value::Number(_)));
+        let method = match call.remove("method") {
+            Some(Value::String(method)) if valid_id && call.get("jsonrpc").and_then(Value::as_str) == Some("2.0") => method,
+            _ => return Some(response(id.filter(|_| valid_id).unwrap_or(Value::Null), Err(RpcError::invalid_request()))),
+        };
+        let params = match call.remove("params") {
+            None => Value::Null,
+            Some(params @ Value::Array(_)) | Some(params @ Value::Object(_)) => params,
+            Some(_) => return id.map(|id| response(id, Err(RpcError::invalid_request()))),
+        };
+        let result = match self.methods.get_mut(&method) {
+            // A panicking handler fails its own call only, not the rest of the batch.
+            Some(handler) => panic::catch_unwind(AssertUnwindSafe(|| handler(webview, params))).unwrap_or_else(|payload| Err(RpcError::internal_error(BindError::panic(&*payload).message))),
+            None => Err(RpcError::method_not_found()),
+        };
+        id.map(|id| response(id, result))
+    }
+}
+
+fn response(id: Value, result: Result<Value, RpcError>) -> Value {
+    let mut response = Map::new();
+    response.insert("jsonrpc".into(), Value::String("2.0".into()));
+    match result {
+        Ok(result) => response.insert("result".into(), result),
+        Err(error) => response.insert("error".into(), serde_json::to_value(error).unwrap_or(Value::Null)),
+    };
+    response.insert("id".into(), id);
+    Value::Object(response)
+}
+
+pub(crate) fn bind<W: WebviewExt + 'static>(webview: &mut W, mut rpc: JsonRpc<W>) -> Result<(), WebviewError> {
+    webview.install_runtime()?;
+    let bridge = webview.bridge();
+    let signatures = rpc.signatures.clone();
+    webview.bind(Cow::Borrowed(RPC_BINDING), Arc::new(RwLock::new(())), move |this: &mut W, id, req, _| {
+        let response = match typed::parse_args::<(Value,)>(req) {
+            Ok((Value::String(message),)) => rpc.handle(this, &message),
+            Ok((message,)) => rpc.handle_value(this, message),
+            Err(_) => Some(response(Value::Null, Err(RpcError::invalid_request()))),
+        };
+        let result = response.and_then(|response| typed::to_js_value(&response).ok()).unwrap_or_else(|| "null".into());
+        let _ = this.return_(Cow::Borrowed(id), STATUS_RESOLVED, Cow::Owned(result));
+    })?;
+    bridge.describe_rpc(signatures);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rpc() -> JsonRpc<()> {
+        JsonRpc::new()
+            .method("add", |_, (a, b): (i32, i32)| Ok::<_, RpcError>(a + b))
+            .method("fail", |_, (): ()| Err::<(), _>("boom"))
+            .method("panic", |_, (): ()| -> Result<(), RpcError> { panic!("handler") })
+    }
+
+    #[test]
+    fn requests_and_notifications() {
+        let mut rpc = rpc();
+        assert_eq!(rpc.handle(&mut (), r#"{"jsonrpc":"2.0","method":"add","params":[1,2],"id":1}"#), Some(json!({"jsonrpc": "2.0", "result": 3, "id": 1})));
+        assert_eq!(rpc.handle(&mut (), r#"{"jsonrpc":"2.0","method":"add","params":[1,2]}"#), None);
+        assert_eq!(rpc.handle(&mut (), r#"[{"jsonrpc":"2.0","method":"add","params":[1,2]}]"#), None);
+    }
+
+    #[test]
+    fn error_codes() {
+        let mut rpc = rpc();
+        let code = |response: Option<Value>| response.unwrap()["error"]["code"].as_i64().unwrap();
+        assert_eq!(code(rpc.handle(&mut (), "{bad")), RpcError::PARSE_ERROR);
+        assert_eq!(code(rpc.handle(&mut (), "[]")), RpcError::INVALID_REQUEST);
+        assert_eq!(code(rpc.handle(&mut (), r#"{"method":"add","id":1}"#)), RpcError::INVALID_REQUEST);
+        assert_eq!(code(rpc.handle(&mut (), r#"{"jsonrpc":"2.0","method":"nope","id":1}"#)), RpcError::METHOD_NOT_FOUND);
+        assert_eq!(code(rpc.handle(&mut (), r#"{"jsonrpc":"2.0","method":"add","params":[1],"id":1}"#)), RpcError::INVALID_PARAMS);
+        assert_eq!(code(rpc.handle(&mut (), r#"{"jsonrpc":"2.0","method":"fail","id":1}"#)), RpcError::SERVER_ERROR);
+    }
+
+    #[test]
+    fn batch_isolates_failures() {
+        let mut rpc = rpc();
+        let batch = json!([1, {"jsonrpc": "2.0", "method": "panic", "id": "p"}, {"jsonrpc": "2.0", "method": "add", "params": [2, 3], "id": "a"}, {"jsonrpc": "2.0", "method": "add", "params": [2, 3]}]);
+        let responses = rpc.handle_value(&mut (), batch).unwrap();
+        assert_eq!(responses.as_array().map(Vec::len), Some(3));
+        assert_eq!(responses[0]["error"]["code"], RpcError::INVALID_REQUEST);
+        assert_eq!(responses[1]["error"]["code"], RpcError::INTERNAL_ERROR);
+        assert_eq!(responses[1]["id"], "p");
+        assert_eq!(responses[2]["result"], 5);
+    }
+}
@@ -0,0 +1,350 @@
+(function () {
+    if (window.plygui) {
+        return;
+    }
+    var handlers = {};
+    var streams = {};
+    var stores = {};
+
+    var BYTES_KEY = '__plygui_bytes';
+    var CALLER_KEY = '__plygui_caller';
+    var TAG_KEY = '__plygui_tag';
+
+    function encodeBytes(bytes) {
+        var binary = '';
+        for (var i = 0; i < bytes.length; i += 0x8000) {
+            binary += String.fromCharCode.apply(null, bytes.subarray(i, i + 0x8000));
+        }
+        var marker = {};
+        marker[BYTES_KEY] = btoa(binary);
+        return marker;
+    }
+
+    function decodeBytes(encoded) {
+        var binary = atob(encoded);
+        var bytes = new Uint8Array(binary.length);
+        for (var i = 0; i < binary.length; i++) {
+            bytes[i] = binary.charCodeAt(i);
+        }
+        return bytes;
+    }
+
+    // Copies `value` with every ArrayBuffer and view replaced by its marker, leaving the page's prototypes alone.
+    function encodeValue(value, parents) {
+        if (value === null || typeof value !== 'object') {
+            return value;
+        }
+        if (typeof ArrayBuffer !== 'undefined') {
+            if (value instanceof ArrayBuffer) {
+                return encodeBytes(new Uint8Array(value));
+            }
+            if (ArrayBuffer.isView(value)) {
+                return encodeBytes(new Uint8Array(value.buffer, value.byteOffset, value.byteLength));
+            }
+        }
+        if (typeof value.toJSON === 'function' || parents.indexOf(value) >= 0) {
+            return value;
+        }
+        parents.push(value);
+        var encoded;
+        if (Array.isArray(value)) {
+            encoded = value.map(function (item) {
+                return encodeValue(item, parents);
+            });
+        } else {
+            encoded = {};
+            Object.keys(value).forEach(function (key) {
+                encoded[key] = encodeValue(value[key], parents);
+            });
+        }
+        parents.pop();
+        return encoded;
+    }
+
+    function newTag() {
+        var bytes = crypto.getRandomValues(new Uint8Array(16));
+        return Array.prototype.map.call(bytes, function (byte) {
+            return (byte < 16 ? '0' : '') + byte.toString(16);
+        }).join('');
+    }
+
+    function invoke(binding, args, tag) {
+        var caller = {};
+        caller[CALLER_KEY] = String(location.href);
+        if (tag) {
+            caller[TAG_KEY] = tag;
+        }
+        return binding.apply(window, encodeValue(Array.prototype.slice.call(args || []), []).concat([caller]));
+    }
+
+    function storeState(name) {
+        return stores[name] = stores[name] || { version: -1, value: undefined, subscribers: [], ready: null };
+    }
+
+    function streamState(id) {
+        return streams[id] = streams[id] || { queue: [], waiting: [], done: false, cancelled: false, error: null };
+    }
+
+    window.plygui = {
+        on: function (name, handler) {
+            (handlers[name] = handlers[name] || []).push(handler);
+            return handler;
+        },
+        off: function (name, handler) {
+            if (!handler) {
+                delete handlers[name];
+            } else if (handlers[name]) {
+                handlers[name] = handlers[name].filter(function (h) { return h !== handler; });
+            }
+        },
+        call: function (name, args, options) {
+            var binding = window[name];
+            var signal = options && options.signal;
+            if (typeof binding !== 'function') {
+                return Promise.reject(new TypeError(name + ' is not a function'));
+            }
+            binding = binding.__plygui || binding;
+            if (!signal) {
+                return invoke(binding, args);
+            }
+            if (signal.aborted) {
+                return Promise.reject(window.plygui.__error({ name: 'AbortError', message: 'The call was aborted', code: 'ABORTED' }));
+            }
+            var tag = newTag();
+            var promise = invoke(binding, args, tag);
+            var abort = function () {
+                window.__plygui_cancel(tag);
+            };
+            var cleanup = function () {
+                signal.removeEventListener('abort', abort);
+            };
+            signal.addEventListener('abort', abort);
+            promise.then(cleanup, cleanup);
+            return promise;
+        },
+        jsonrpc: function (message) {
+            return invoke(window.__plygui_jsonrpc, [message]).then(function (response) {
+                return typeof message === 'string' && response !== null ? JSON.stringify(response) : response;
+            });
+        },
+        __wrap: function (name) {
+            var binding = window[name];
+            if (typeof binding !== 'function' || binding.__plygui) {
+                return;
+            }
+            var wrapper = function () {
+                return invoke(binding, arguments);
+            };
+            wrapper.__plygui = binding;
+            window[name] = wrapper;
+        },
+        __namespace: function (path, methods) {
+            var target = window;
+            path.split('.').forEach(function (part) {
+                target = target[part] = target[part] || {};
+            });
+            methods.forEach(function (method) {
+                var name = path + '.' + method;
+                target[method] = function () {
+                    return window[name].apply(window, arguments);
+                };
+            });
+        },
+        __error: function (info) {
+            if (!info || typeof info !== 'object') {
+                return new Error(String(info));
+            }
+            var error = new Error(info.message);
+            error.name = info.name || 'Error';
+            if (info.code !== undefined) {
+                error.code = info.code;
+            }
+            if (info.data !== undefined) {
+                error.data = info.data;
+            }
+            return error;
+        },
+        __errorInfo: function (e) {
+            if (e instanceof Error) {
+                return { name: e.name, message: e.message, stack: e.stack || null };
+            }
+            return { name: 'Error', message: String(e), stack: null };
+        },
+        __decode: function (value) {
+            if (value && typeof value === 'object') {
+                var keys = Object.keys(value);
+                if (keys.length === 1 && keys[0] === BYTES_KEY && typeof value[BYTES_KEY] === 'string') {
+                    return decodeBytes(value[BYTES_KEY]);
+                }
+                keys.forEach(function (key) {
+                    value[key] = window.plygui.__decode(value[key]);
+                });
+            }
+            return value;
+        },
+        __json: function (value) {
+            var json = JSON.stringify(encodeValue(value, []));
+            return json === undefined ? null : JSON.parse(json);
+        },
+        __call: function (path, args) {
+            var target = window, owner = window;
+            path.split('.').forEach(function (part) {
+                if (target === undefined || target === null) {
+                    throw new TypeError(path + ' is not defined');
+                }
+                owner = target;
+                target = target[part];
+            });
+            if (typeof target !== 'function') {
+                throw new TypeError(path + ' is not a function');
+            }
+            return target.apply(owner, args);
+        },
+        __evalResult: function (binding, token, run) {
+            new Promise(function (resolve) {
+                resolve(run());
+            }).then(function (value) {
+                window[binding](token, true, window.plygui.__json(value));
+            }).catch(function (e) {
+                window[binding](token, false, window.plygui.__errorInfo(e));
+            });
+        },
+        store: function (name) {
+            var state = storeState(name);
+            var set = function (value) {
+                return invoke(window.__plygui_store_set, [name, value]).then(function () {
+                    return state.value;
+                });
+            };
+            return {
+                get: function () {
+                    return state.value;
+                },
+                version: function () {
+                    return state.version;
+                },
+                ready: function () {
+                    return state.ready || Promise.resolve(state.value);
+                },
+                set: set,
+                update: function (updater) {
+                    return set(updater(state.value));
+                },
+                subscribe: function (subscriber) {
+                    state.subscribers.push(subscriber);
+                    return function () {
+                        state.subscribers = state.subscribers.filter(function (s) { return s !== subscriber; });
+                    };
+                }
+            };
+        },
+        __storeDeclare: function (name) {
+            var state = storeState(name);
+            state.ready = invoke(window.__plygui_store_get, [name]).then(function (snapshot) {
+                window.plygui.__storeUpdate(name, snapshot[0], snapshot[1]);
+                return state.value;
+            }, function () {
+                return state.value;
+            });
+        },
+        __storeUpdate: function (name, version, value) {
+            var state = storeState(name);
+            if (version <= state.version) {
+                return;
+            }
+            state.version = version;
+            state.value = value;
+            state.subscribers.slice().forEach(function (subscriber) {
+                try {
+                    subscriber(value, version);
+                } catch (e) {
+                    console.error(e);
+                }
+            });
+        },
+        __openStream: function (id, key) {
+            var state = streamState(id);
+            var next = function () {
+                if (state.queue.length) {
+                    return Promise.resolve({ value: state.queue.shift(), done: false });
+                }
+                if (state.done) {
+                    delete streams[id];
+                    return state.error ? Promise.reject(state.error) : Promise.resolve({ value: undefined, done: true });
+                }
+                return new Promise(function (resolve, reject) {
+                    state.waiting.push({ resolve: resolve, reject: reject });
+                });
+            };
+            var cancel = function () {
+                if (!state.done) {
+                    state.done = state.cancelled = true;
+                    state.queue = [];
+                    state.waiting.splice(0).forEach(function (waiter) {
+                        waiter.resolve({ value: undefined, done: true });
+                    });
+                    window.__plygui_stream_cancel(id, key);
+                }
+                return Promise.resolve({ value: undefined, done: true });
+            };
+            var stream = { next: next, return: cancel, cancel: cancel };
+            if (typeof Symbol !== 'undefined' && Symbol.asyncIterator) {
+                stream[Symbol.asyncIterator] = function () {
+                    return stream;
+                };
+            }
+            if (typeof ReadableStream !== 'undefined') {
+                stream.readable = function () {
+                    return new ReadableStream({
+                        pull: function (controller) {
+                            return next().then(function (result) {
+                                if (result.done) {
+                                    controller.close();
+                                } else {
+                                    controller.enqueue(result.value);
+                                }
+                            });
+                        },
+                        cancel: cancel
+                    });
+                };
+            }
+            return stream;
+        },
+        __stream: function (id, kind, value) {
+            var state = streamState(id);
+            if (state.cancelled) {
+                if (kind !== 'next') {
+                    delete streams[id];
+                }
+                return;
+            }
+            if (kind === 'next') {
+                if (state.waiting.length) {
+                    state.waiting.shift().resolve({ value: value, done: false });
+                } else {
+                    state.queue.push(value);
+                }
+                return;
+            }
+            state.done = true;
+            state.error = kind === 'error' ? window.plygui.__error(value) : null;
+            state.waiting.splice(0).forEach(function (waiter) {
+                if (state.error) {
+                    waiter.reject(state.error);
+                } else {
+                    waiter.resolve({ value: undefined, done: true });
+                }
+            });
+        },
+        __emit: function (name, payload) {
+            (handlers[name] || []).slice().forEach(function (handler) {
+                try {
+                    handler(payload);
+                } catch (e) {
+                    console.error(e);
+                }
+            });
+        }
+    };
+})();
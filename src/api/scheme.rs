@@ -0,0 +1,115 @@
+use super::webview::WebviewError;
+
+use std::sync::Arc;
+
+/// Schemes the engines handle themselves and that cannot be served from Rust.
+const RESERVED: &[&str] = &["about", "blob", "data", "file", "ftp", "http", "https", "javascript", "resource", "ws", "wss"];
+
+/// Request the page made to a scheme registered with `WebviewExt::register_scheme`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemeRequest {
+    pub method: String,
+    pub uri: String,
+    pub scheme: String,
+    /// Authority of the URI, e.g. `app` for `app://app/index.html`; empty for URIs like `app:/index.html`.
+    pub host: String,
+    /// Still percent-encoded, without the query and fragment, always starting with `/`.
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+}
+
+impl SchemeRequest {
+    pub fn new<S: Into<String>>(method: S, uri: S) -> Self {
+        let uri = uri.into();
+        let (scheme, rest) = match uri.find(':') {
+            Some(colon) => (uri[..colon].to_ascii_lowercase(), &uri[colon + 1..]),
+            None => (String::new(), &uri[..]),
+        };
+        let rest = rest.split(['?', '#']).next().unwrap_or("");
+        let (host, path) = match rest.strip_prefix("//") {
+            Some(authority) => match authority.find('/') {
+                Some(slash) => (&authority[..slash], &authority[slash..]),
+                None => (authority, ""),
+            },
+            None => ("", rest),
+        };
+        let path = if path.starts_with('/') { path.to_string() } else { format!("/{}", path) };
+        SchemeRequest { method: method.into(), scheme, host: host.to_ascii_lowercase(), path, uri, headers: Vec::new() }
+    }
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(header, _)| header.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+    }
+    pub fn query(&self) -> Option<&str> {
+        let uri = self.uri.split('#').next().unwrap_or("");
+        uri.find('?').map(|question| &uri[question + 1..])
+    }
+}
+
+/// Answer to a `SchemeRequest`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemeResponse {
+    pub status: u16,
+    pub mime_type: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl SchemeResponse {
+    pub fn new<S: Into<String>, B: Into<Vec<u8>>>(status: u16, mime_type: S, body: B) -> Self {
+        SchemeResponse { status, mime_type: mime_type.into(), headers: Vec::new(), body: body.into() }
+    }
+    pub fn ok<S: Into<String>, B: Into<Vec<u8>>>(mime_type: S, body: B) -> Self {
+        SchemeResponse::new(200, mime_type, body)
+    }
+    pub fn not_found() -> Self {
+        SchemeResponse::new(404, "text/plain", "Not Found")
+    }
+    pub fn with_header<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+}
+
+pub type SchemeHandler = Arc<dyn Fn(&SchemeRequest) -> SchemeResponse + Send + Sync>;
+
+/// Validates a scheme name as RFC 3986 defines it and returns it lowercased.
+pub fn check_scheme(scheme: &str) -> Result<String, WebviewError> {
+    let scheme = scheme.trim_end_matches("://").trim_end_matches(':').to_ascii_lowercase();
+    let mut chars = scheme.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic()) && chars.all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.');
+    if !valid || RESERVED.contains(&scheme.as_str()) {
+        return Err(WebviewError::InvalidArgument);
+    }
+    Ok(scheme)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scheme_names() {
+        for (scheme, expected) in &[("app", Some("app")), ("App", Some("app")), ("app://", Some("app")), ("app:", Some("app")), ("my-app+v1.2", Some("my-app+v1.2"))] {
+            assert_eq!(check_scheme(scheme).ok().as_deref(), *expected, "{}", scheme);
+        }
+        for scheme in &["", "://", "1app", "-app", "my app", "app/x", "ãpp", "http", "HTTPS", "file", "File://", "data", "javascript"] {
+            assert!(check_scheme(scheme).is_err(), "{}", scheme);
+        }
+    }
+
+    #[test]
+    fn request_parts() {
+        let cases = [
+            ("app://App/assets/x.js?v=1#top", "app", "app", "/assets/x.js", Some("v=1")),
+            ("APP://host", "app", "host", "/", None),
+            ("app:index.html", "app", "", "/index.html", None),
+            ("app:/index.html#a?b", "app", "", "/index.html", None),
+            ("app://host/a%20b?", "app", "host", "/a%20b", Some("")),
+            ("", "", "", "/", None),
+        ];
+        for (uri, scheme, host, path, query) in &cases {
+            let request = SchemeRequest::new("GET", *uri);
+            assert_eq!((request.scheme.as_str(), request.host.as_str(), request.path.as_str(), request.query()), (*scheme, *host, *path, *query), "{}", uri);
+        }
+    }
+}
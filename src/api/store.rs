@@ -0,0 +1,253 @@
+use super::bridge::WebviewBridge;
+use super::typed::{self, BindError, STATUS_RESOLVED};
+use super::webview::{WebviewError, WebviewExt};
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::{self, Debug};
+use std::sync::{Arc, Mutex, RwLock};
+
+pub const STORE_GET_BINDING: &str = "__plygui_store_get";
+pub const STORE_SET_BINDING: &str = "__plygui_store_set";
+
+/// Side that changed a `SharedStore`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StoreChange {
+    Rust,
+    Page,
+}
+
+type Subscriber<T> = Arc<dyn Fn(&T, StoreChange) + Send + Sync>;
+type Subscribers<T> = (u64, Vec<(u64, Subscriber<T>)>);
+
+/// State shared between Rust and the pages of the webviews it is registered on, see `WebviewExt::register_store`.
+/// In JS it is available as `window.plygui.store(name)`.
+pub struct SharedStore<T> {
+    inner: Arc<StoreInner<T>>,
+}
+
+struct StoreInner<T> {
+    name: String,
+    state: RwLock<(u64, T)>,
+    subscribers: Mutex<Subscribers<T>>,
+    bridges: Mutex<Vec<WebviewBridge>>,
+}
+
+impl<T> Clone for SharedStore<T> {
+    fn clone(&self) -> Self {
+        SharedStore { inner: self.inner.clone() }
+    }
+}
+
+impl<T: Clone + Serialize + DeserializeOwned + Send + Sync + 'static> SharedStore<T> {
+    pub fn new<S: Into<String>>(name: S, value: T) -> Self {
+        SharedStore {
+            inner: Arc::new(StoreInner { name: name.into(), state: RwLock::new((0, value)), subscribers: Mutex::new((0, Vec::new())), bridges: Mutex::new(Vec::new()) }),
+        }
+    }
+    pub fn name(&self) -> &str {
+        &self.inner.name
+    }
+    pub fn version(&self) -> u64 {
+        self.inner.state.read().unwrap().0
+    }
+    pub fn get(&self) -> T {
+        self.inner.state.read().unwrap().1.clone()
+    }
+    pub fn read<R, F: FnOnce(&T) -> R>(&self, f: F) -> R {
+        f(&self.inner.state.read().unwrap().1)
+    }
+    pub fn set(&self, value: T) {
+        self.update(move |current| *current = value)
+    }
+    /// Changes the value and notifies the Rust subscribers and every registered page.
+    pub fn update<F: FnOnce(&mut T)>(&self, f: F) {
+        self.commit(StoreChange::Rust, f);
+    }
+    /// Calls `subscriber` after every change, whichever side made it. Returns the id `unsubscribe` takes.
+    pub fn subscribe<F: Fn(&T, StoreChange) + Send + Sync + 'static>(&self, subscriber: F) -> u64 {
+        let mut subscribers = self.inner.subscribers.lock().unwrap();
+        subscribers.0 += 1;
+        let id = subscribers.0;
+        subscribers.1.push((id, Arc::new(subscriber)));
+        id
+    }
+    pub fn unsubscribe(&self, id: u64) -> bool {
+        let mut subscribers = self.inner.subscribers.lock().unwrap();
+        let len = subscribers.1.len();
+        subscribers.1.retain(|(subscriber, _)| *subscriber != id);
+        subscribers.1.len() != len
+    }
+    fn commit<F: FnOnce(&mut T)>(&self, change: StoreChange, f: F) -> u64 {
+        let (version, value) = {
+            let mut state = self.inner.state.write().unwrap();
+            f(&mut state.1);
+            state.0 += 1;
+            (state.0, state.1.clone())
+        };
+        let subscribers = self.inner.subscribers.lock().unwrap().1.iter().map(|(_, subscriber)| subscriber.clone()).collect::<Vec<_>>();
+        for subscriber in subscribers {
+            subscriber(&value, change);
+        }
+        if let Ok(script) = update_script(&self.inner.name, version, &value) {
+            let bridges = self.inner.bridges.lock().unwrap().clone();
+            for bridge in bridges {
+                let _ = bridge.eval(&script);
+            }
+        }
+        version
+    }
+    fn attach(&self, bridge: WebviewBridge) {
+        let mut bridges = self.inner.bridges.lock().unwrap();
+        if !bridges.iter().any(|attached| attached.same(&bridge)) {
+            bridges.push(bridge);
+        }
+    }
+}
+
+impl<T> Debug for SharedStore<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedStore").field("name", &self.inner.name).finish()
+    }
+}
+
+fn update_script<T: Serialize>(name: &str, version: u64, value: &T) -> Result<String, WebviewError> {
+    Ok(format!(
+        "window.plygui && window.plygui.__storeUpdate({}, {}, {});",
+        typed::to_js_json(name).map_err(|_| WebviewError::InvalidArgument)?,
+        version,
+        typed::to_js_value(value).map_err(|_| WebviewError::InvalidArgument)?
+    ))
+}
+
+/// Type-erased store as seen by the bindings serving the page.
+trait StoreEntry: Send + Sync {
+    fn snapshot(&self) -> Result<String, WebviewError>;
+    fn assign(&self, value: Value) -> Result<u64, BindError>;
+    fn detach(&self, bridge: &WebviewBridge);
+}
+
+impl<T: Clone + Serialize + DeserializeOwned + Send + Sync + 'static> StoreEntry for SharedStore<T> {
+    fn snapshot(&self) -> Result<String, WebviewError> {
+        let state = self.inner.state.read().unwrap();
+        typed::to_js_value(&(state.0, &state.1)).map_err(|_| WebviewError::InvalidArgument)
+    }
+    fn assign(&self, value: Value) -> Result<u64, BindError> {
+        let value = serde_json::from_value::<T>(value).map_err(BindError::invalid_arguments)?;
+        Ok(self.commit(StoreChange::Page, move |current| *current = value))
+    }
+    fn detach(&self, bridge: &WebviewBridge) {
+        self.inner.bridges.lock().unwrap().retain(|attached| !attached.same(bridge));
+    }
+}
+
+type Stores = RwLock<HashMap<String, Arc<dyn StoreEntry>>>;
+
+fn install<W: WebviewExt>(webview: &mut W) -> Result<Arc<Stores>, WebviewError> {
+    webview.install_runtime()?;
+    let bridge = webview.bridge();
+    let (stores, created) = bridge.extension::<Stores, _>(|| RwLock::new(HashMap::new()));
+    if created {
+        let context = Arc::new(RwLock::new(()));
+        let registry = stores.clone();
+        let res = webview.bind(Cow::Borrowed(STORE_GET_BINDING), context.clone(), move |this: &mut W, id, req, _| {
+            let entry = typed::parse_args::<(String,)>(req).ok().and_then(|(name,)| registry.read().unwrap().get(&name).cloned());
+            let (status, result) = match entry.map(|entry| entry.snapshot()) {
+                Some(Ok(snapshot)) => (STATUS_RESOLVED, snapshot),
+                Some(Err(e)) => typed::rejection(BindError::invalid_result(format!("{:?}", e))),
+                None => typed::rejection(BindError::new("ReferenceError", "No such store").with_code("NOT_FOUND")),
+            };
+            let _ = this.return_(Cow::Borrowed(id), status, Cow::Owned(result));
+        });
+        let registry = stores.clone();
+        let res = res.and_then(|_| {
+            webview.bind(Cow::Borrowed(STORE_SET_BINDING), context, move |this: &mut W, id, req, _| {
+                let (status, result) = match typed::parse_args::<(String, Value)>(req) {
+                    Ok((name, value)) => match registry.read().unwrap().get(&name).cloned() {
+                        Some(entry) => match entry.assign(value) {
+                            Ok(version) => (STATUS_RESOLVED, version.to_string()),
+                            Err(e) => typed::rejection(e),
+                        },
+                        None => typed::rejection(BindError::new("ReferenceError", "No such store").with_code("NOT_FOUND")),
+                    },
+                    Err(e) => typed::rejection(BindError::invalid_arguments(e)),
+                };
+                let _ = this.return_(Cow::Borrowed(id), status, Cow::Owned(result));
+            })
+        });
+        if let Err(e) = res {
+            let _ = webview.unbind(Cow::Borrowed(STORE_GET_BINDING));
+            bridge.remove_extension::<Stores>();
+            return Err(e);
+        }
+    }
+    Ok(stores)
+}
+
+pub(crate) fn register<W, T>(webview: &mut W, store: &SharedStore<T>) -> Result<(), WebviewError>
+where
+    W: WebviewExt,
+    T: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    let stores = install(webview)?;
+    let declare = format!("window.plygui.__storeDeclare({});", typed::to_js_json(store.name()).map_err(|_| WebviewError::InvalidArgument)?);
+    stores.write().unwrap().insert(store.name().into(), Arc::new(store.clone()));
+    store.attach(webview.bridge());
+    webview.init(Cow::Borrowed(&declare))?;
+    webview.eval(Cow::Owned(declare))
+}
+
+/// Stops syncing the store `name` with the webview. The page keeps its last value.
+pub(crate) fn unregister<W: WebviewExt>(webview: &mut W, name: &str) -> Result<(), WebviewError> {
+    let bridge = webview.bridge();
+    let stores = bridge.get_extension::<Stores>().ok_or(WebviewError::NotFound)?;
+    let entry = stores.write().unwrap().remove(name).ok_or(WebviewError::NotFound)?;
+    entry.detach(&bridge);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn changes_bump_the_version() {
+        let store = SharedStore::new("count", 0u32);
+        assert_eq!(store.version(), 0);
+        store.set(1);
+        store.update(|count| *count += 1);
+        assert_eq!((store.version(), store.get()), (2, 2));
+        assert_eq!(store.snapshot().unwrap(), "[2,2]");
+    }
+
+    #[test]
+    fn subscribers_see_every_change() {
+        let store = SharedStore::new("todos", vec![1u32]);
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let recorder = seen.clone();
+        let id = store.subscribe(move |todos: &Vec<u32>, change| recorder.lock().unwrap().push((todos.clone(), change)));
+        store.update(|todos| todos.push(2));
+        store.assign(json!([3])).unwrap();
+        assert!(store.unsubscribe(id));
+        assert!(!store.unsubscribe(id));
+        store.set(Vec::new());
+        assert_eq!(*seen.lock().unwrap(), [(vec![1, 2], StoreChange::Rust), (vec![3], StoreChange::Page)]);
+    }
+
+    #[test]
+    fn page_changes_reach_rust() {
+        let store = SharedStore::new("user", json!(null));
+        store.attach(WebviewBridge::new(std::ptr::null_mut()));
+        assert_eq!(store.assign(json!({ "name": "Ann" })).unwrap(), 1);
+        assert_eq!(store.get(), json!({ "name": "Ann" }));
+
+        let typed = SharedStore::new("count", 0u32);
+        assert!(typed.assign(json!("many")).is_err());
+        assert_eq!((typed.version(), typed.get()), (0, 0));
+    }
+}
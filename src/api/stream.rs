@@ -0,0 +1,191 @@
+use super::bridge::WebviewBridge;
+use super::calls;
+use super::typed::{self, BindError, IntoBindError, STATUS_RESOLVED};
+use super::typescript::{TsArgs, TsSignature, TsType};
+use super::webview::{WebviewBindContext, WebviewError, WebviewExt};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::{self, Debug};
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+
+pub const STREAM_CANCEL_BINDING: &str = "__plygui_stream_cancel";
+
+/// Open streams by id, with their binding, the random key the page must show to cancel them, and their cancel flag.
+struct Streams {
+    next: u64,
+    active: HashMap<u64, (String, String, Arc<AtomicBool>)>,
+}
+
+impl Streams {
+    /// Cancels the streams of `binding`, or every stream when the page they were feeding is gone.
+    fn cancel(&mut self, binding: Option<&str>) {
+        self.active.retain(|_, (name, _, cancelled)| {
+            let reset = binding.is_none_or(|binding| binding == name);
+            if reset {
+                cancelled.store(true, Ordering::SeqCst);
+            }
+            !reset
+        });
+    }
+}
+
+fn install<W: WebviewExt>(webview: &mut W) -> Result<Arc<Mutex<Streams>>, WebviewError> {
+    webview.install_runtime()?;
+    let bridge = webview.bridge();
+    let (streams, created) = bridge.extension(|| Mutex::new(Streams { next: 0, active: HashMap::new() }));
+    if created {
+        let registry = streams.clone();
+        let res = webview.bind(Cow::Borrowed(STREAM_CANCEL_BINDING), Arc::new(RwLock::new(())), move |this: &mut W, id, req, _| {
+            let _ = this.return_(Cow::Borrowed(id), STATUS_RESOLVED, Cow::Borrowed("null"));
+            if let Ok((stream, key)) = typed::parse_args::<(u64, String)>(req) {
+                if let Some((_, _, cancelled)) = registry.lock().unwrap().active.get(&stream).filter(|(_, expected, _)| *expected == key) {
+                    cancelled.store(true, Ordering::SeqCst);
+                }
+            }
+        });
+        if let Err(e) = res {
+            bridge.remove_extension::<Mutex<Streams>>();
+            return Err(e);
+        }
+        let streams = Arc::downgrade(&streams);
+        bridge.on_reset(move |binding| {
+            if let Some(streams) = streams.upgrade() {
+                streams.lock().unwrap().cancel(binding);
+            }
+        });
+    }
+    Ok(streams)
+}
+
+pub(crate) fn bind<W, C, A, T, F>(webview: &mut W, name: Cow<str>, context: Arc<RwLock<C>>, mut callback: F) -> Result<(), WebviewError>
+where
+    W: WebviewExt,
+    C: WebviewBindContext,
+    A: DeserializeOwned,
+    T: Serialize,
+    F: FnMut(&mut W, A, StreamSink<T>, &mut C),
+{
+    let streams = install(webview)?;
+    let bridge = webview.bridge();
+    let binding = name.clone().into_owned();
+    webview.bind(name.clone(), context, move |this, id, req, context| {
+        let args = match typed::parse_args::<A>(req) {
+            Ok(args) => args,
+            Err(e) => {
+                let (status, result) = typed::rejection(BindError::invalid_arguments(e));
+                let _ = this.return_(Cow::Borrowed(id), status, Cow::Owned(result));
+                return;
+            }
+        };
+        let (sink, key) = match StreamSink::open(this.bridge(), streams.clone(), &binding) {
+            Ok(opened) => opened,
+            Err(e) => {
+                let (status, result) = typed::rejection(BindError::new("Error", format!("Could not open the stream: {:?}", e)));
+                let _ = this.return_(Cow::Borrowed(id), status, Cow::Owned(result));
+                return;
+            }
+        };
+        let opened = format!("window.plygui.__openStream({}, \"{}\")", sink.id, key);
+        if this.return_(Cow::Borrowed(id), STATUS_RESOLVED, Cow::Owned(opened)).is_ok() {
+            callback(this, args, sink, context);
+        } else {
+            sink.forget();
+        }
+    })?;
+    bridge.describe(&name, TsSignature { args: A::ts_args(), result: format!("PlyguiStream<{}>", T::ts_type()) });
+    typed::expose(webview, &name)
+}
+
+/// Pushes the chunks of a streaming call to the JS async iterator it resolved to. Dropping it unfinished errors the stream.
+pub struct StreamSink<T> {
+    id: u64,
+    bridge: WebviewBridge,
+    streams: Arc<Mutex<Streams>>,
+    cancelled: Arc<AtomicBool>,
+    closed: bool,
+    _chunk: PhantomData<fn(&T)>,
+}
+
+impl<T: Serialize> StreamSink<T> {
+    /// Opens a stream of `binding`, returning it along with the key the page cancels it with.
+    fn open(bridge: WebviewBridge, streams: Arc<Mutex<Streams>>, binding: &str) -> Result<(Self, String), WebviewError> {
+        let key = calls::new_token()?;
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let id = {
+            let mut streams = streams.lock().unwrap();
+            streams.next += 1;
+            let id = streams.next;
+            streams.active.insert(id, (binding.into(), key.clone(), cancelled.clone()));
+            id
+        };
+        Ok((StreamSink { id, bridge, streams, cancelled, closed: false, _chunk: PhantomData }, key))
+    }
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+    /// Whether the page stopped consuming the stream, navigated away or lost the binding. Further chunks fail with `WebviewError::Canceled`.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+    pub fn send(&self, chunk: &T) -> Result<(), WebviewError> {
+        if self.is_cancelled() {
+            return Err(WebviewError::Canceled);
+        }
+        let chunk = typed::to_js_value(chunk).map_err(|_| WebviewError::InvalidArgument)?;
+        self.bridge.eval(&stream_script(self.id, "next", &chunk))
+    }
+    pub fn finish(mut self) -> Result<(), WebviewError> {
+        self.close("done", "null")
+    }
+    pub fn error<E: IntoBindError>(mut self, error: E) -> Result<(), WebviewError> {
+        let error = typed::to_js_json(&error.into_bind_error()).map_err(|_| WebviewError::InvalidArgument)?;
+        self.close("error", &error)
+    }
+    fn forget(mut self) {
+        self.closed = true;
+        self.streams.lock().unwrap().active.remove(&self.id);
+    }
+}
+
+impl<T> StreamSink<T> {
+    fn close(&mut self, kind: &str, value: &str) -> Result<(), WebviewError> {
+        self.closed = true;
+        self.streams.lock().unwrap().active.remove(&self.id);
+        if self.cancelled.load(Ordering::SeqCst) {
+            // The page already closed its end, or is not the page the stream was opened for anymore.
+            return Ok(());
+        }
+        self.bridge.eval(&stream_script(self.id, kind, value))
+    }
+}
+
+fn stream_script(id: u64, kind: &str, value: &str) -> String {
+    format!("window.plygui && window.plygui.__stream({}, \"{}\", {});", id, kind, value)
+}
+
+impl<T> Drop for StreamSink<T> {
+    fn drop(&mut self) {
+        if !self.closed {
+            let error = if thread::panicking() {
+                BindError::new("Panic", "Stream handler panicked").with_code("PANIC")
+            } else {
+                BindError::new("Error", "Stream was dropped without being finished").with_code("DROPPED")
+            };
+            if let Ok(error) = typed::to_js_json(&error) {
+                let _ = self.close("error", &error);
+            }
+        }
+    }
+}
+
+impl<T> Debug for StreamSink<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamSink").field("id", &self.id).field("cancelled", &self.cancelled.load(Ordering::SeqCst)).finish()
+    }
+}
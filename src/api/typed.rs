@@ -0,0 +1,186 @@
+use super::bytes::BYTES_KEY;
+use super::eval::JsError;
+use super::rpc::RpcError;
+use super::webview::{WebviewError, WebviewExt};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use std::any::Any;
+use std::borrow::Cow;
+use std::fmt::{self, Display};
+
+pub const STATUS_RESOLVED: i32 = 0;
+pub const STATUS_REJECTED: i32 = 1;
+
+/// Error delivered to JS as an `Error` object carrying `name`, `message`, `code` and `data`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BindError {
+    pub name: String,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl BindError {
+    pub fn new<N: Into<String>, M: Into<String>>(name: N, message: M) -> Self {
+        BindError { name: name.into(), message: message.into(), code: None, data: None }
+    }
+    pub fn with_code<C: Into<String>>(mut self, code: C) -> Self {
+        self.code = Some(code.into());
+        self
+    }
+    pub fn with_data(mut self, data: Value) -> Self {
+        self.data = Some(data);
+        self
+    }
+    pub fn invalid_arguments<D: Display>(details: D) -> Self {
+        BindError::new("TypeError", format!("Invalid arguments: {}", details)).with_code("INVALID_ARGUMENTS")
+    }
+    pub fn invalid_result<D: Display>(details: D) -> Self {
+        BindError::new("TypeError", format!("Invalid result: {}", details)).with_code("INVALID_RESULT")
+    }
+    pub fn panic(payload: &(dyn Any + Send)) -> Self {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "Binding handler panicked".into());
+        BindError::new("Panic", message).with_code("PANIC")
+    }
+}
+
+impl Display for BindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.name, self.message)
+    }
+}
+
+impl std::error::Error for BindError {}
+
+/// Conversion of handler errors into `BindError`. Any `Display` type becomes a plain JS `Error`.
+pub trait IntoBindError {
+    fn into_bind_error(self) -> BindError;
+}
+
+impl<E: Display> IntoBindError for E {
+    default fn into_bind_error(self) -> BindError {
+        BindError::new("Error", self.to_string())
+    }
+}
+impl IntoBindError for BindError {
+    fn into_bind_error(self) -> BindError {
+        self
+    }
+}
+impl IntoBindError for JsError {
+    fn into_bind_error(self) -> BindError {
+        let error = BindError::new(self.name, self.message);
+        match self.stack {
+            Some(stack) => error.with_data(Value::String(stack)),
+            None => error,
+        }
+    }
+}
+impl IntoBindError for RpcError {
+    fn into_bind_error(self) -> BindError {
+        BindError { name: "RpcError".into(), message: self.message, code: Some(self.code.to_string()), data: self.data }
+    }
+}
+
+/// Serializes `value` into JSON that can be spliced into a JS source string as-is.
+pub fn to_js_json<T: Serialize + ?Sized>(value: &T) -> Result<String, serde_json::Error> {
+    serde_json::to_string(value).map(|json| json.replace('\u{2028}', "\\u2028").replace('\u{2029}', "\\u2029"))
+}
+
+/// Like `to_js_json`, but binary payloads in `value` arrive in JS as `Uint8Array`s.
+pub fn to_js_value<T: Serialize + ?Sized>(value: &T) -> Result<String, serde_json::Error> {
+    let json = to_js_json(value)?;
+    if json.contains(&format!("\"{}\"", BYTES_KEY)) {
+        Ok(format!("window.plygui.__decode({})", json))
+    } else {
+        Ok(json)
+    }
+}
+
+/// Deserializes the JS call's argument array into `A`. Calls without arguments also match `()`.
+pub fn parse_args<A: DeserializeOwned>(req: &str) -> Result<A, serde_json::Error> {
+    args_from_value(serde_json::from_str(req)?)
+}
+
+pub fn args_from_value<A: DeserializeOwned>(args: Value) -> Result<A, serde_json::Error> {
+    let empty = args.as_array().map(|args| args.is_empty()).unwrap_or(false);
+    A::deserialize(args).or_else(|e| if empty { A::deserialize(Value::Null).map_err(|_| e) } else { Err(e) })
+}
+
+/// Routes the page's calls to `name` through the runtime, which reports the calling frame with each of them.
+pub(crate) fn expose<W: WebviewExt>(webview: &mut W, name: &str) -> Result<(), WebviewError> {
+    let script = format!("window.plygui.__wrap({});", to_js_json(name).map_err(|_| WebviewError::InvalidArgument)?);
+    webview.init(Cow::Borrowed(&script))?;
+    webview.eval(Cow::Owned(script))
+}
+
+pub(crate) const REJECTION_PREFIX: &str = "(function (e) { return window.plygui ? window.plygui.__error(e) : e; })(";
+pub(crate) const REJECTION_SUFFIX: &str = ")";
+
+pub(crate) fn rejection<E: IntoBindError>(error: E) -> (i32, String) {
+    let error = to_js_json(&error.into_bind_error()).unwrap_or_else(|_| "null".into());
+    (STATUS_REJECTED, format!("{}{}{}", REJECTION_PREFIX, error, REJECTION_SUFFIX))
+}
+
+pub(crate) fn invoke<A, R, E, F>(req: &str, callback: F) -> (i32, String)
+where
+    A: DeserializeOwned,
+    R: Serialize,
+    E: IntoBindError,
+    F: FnOnce(A) -> Result<R, E>,
+{
+    let args = match parse_args::<A>(req) {
+        Ok(args) => args,
+        Err(e) => return rejection(BindError::invalid_arguments(e)),
+    };
+    match callback(args) {
+        Ok(value) => match to_js_value(&value) {
+            Ok(json) => (STATUS_RESOLVED, json),
+            Err(e) => rejection(BindError::invalid_result(e)),
+        },
+        Err(e) => rejection(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rejected(answer: (i32, String)) -> BindError {
+        assert_eq!(answer.0, STATUS_REJECTED);
+        let json = answer.1.strip_prefix(REJECTION_PREFIX).and_then(|json| json.strip_suffix(REJECTION_SUFFIX)).unwrap();
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn empty_args_match_unit() {
+        assert_eq!(parse_args::<()>("[]").ok(), Some(()));
+        assert_eq!(parse_args::<Vec<i32>>("[]").ok(), Some(Vec::new()));
+        assert!(parse_args::<(i32,)>("[]").is_err());
+    }
+
+    #[test]
+    fn wrong_args_are_rejected() {
+        let error = rejected(invoke::<(i32, i32), i32, String, _>(r#"[1, "2"]"#, |_| panic!("called with bad arguments")));
+        assert_eq!(error.code.as_deref(), Some("INVALID_ARGUMENTS"));
+        let error = rejected(invoke::<(i32,), i32, String, _>("[1, 2]", |_| panic!("called with bad arguments")));
+        assert_eq!(error.code.as_deref(), Some("INVALID_ARGUMENTS"));
+        assert_eq!(invoke::<(i32, i32), i32, String, _>("[1, 2]", |(a, b)| Ok(a + b)), (STATUS_RESOLVED, "3".into()));
+    }
+
+    #[test]
+    fn errors_are_rejected() {
+        let error = rejected(invoke::<(), (), String, _>("[]", |_| Err("boom".into())));
+        assert_eq!((error.name.as_str(), error.message.as_str(), error.code), ("Error", "boom", None));
+        let error = rejected(invoke::<(), (), BindError, _>("[]", |_| Err(BindError::new("NotFound", "nope").with_code("ENOENT"))));
+        assert_eq!(error, BindError::new("NotFound", "nope").with_code("ENOENT"));
+    }
+}
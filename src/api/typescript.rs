@@ -0,0 +1,301 @@
+use super::namespace::NAMESPACE_ROOT;
+
+use serde_json::Value;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt::Write;
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// TypeScript type of a value crossing the bridge. Types without a specialized impl are declared as `any`; see `ts_interface!` for describing your own.
+pub trait TsType {
+    fn ts_type() -> String;
+}
+
+/// TypeScript parameter list of a binding's argument tuple.
+pub trait TsArgs {
+    fn ts_args() -> String;
+}
+
+impl<T: ?Sized> TsType for T {
+    default fn ts_type() -> String {
+        "any".into()
+    }
+}
+impl<T> TsArgs for T {
+    default fn ts_args() -> String {
+        "...args: any[]".into()
+    }
+}
+
+macro_rules! ts_type {
+    ($ts:expr => $($t:ty),+) => {
+        $(impl TsType for $t {
+            fn ts_type() -> String {
+                $ts.into()
+            }
+        })+
+    };
+}
+ts_type!("boolean" => bool);
+ts_type!("number" => i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize, f32, f64);
+ts_type!("string" => str, String, char);
+ts_type!("void" => ());
+ts_type!("any" => Value);
+
+impl<T: TsType> TsType for Option<T> {
+    fn ts_type() -> String {
+        format!("{} | null", T::ts_type())
+    }
+}
+impl<T: TsType> TsType for [T] {
+    fn ts_type() -> String {
+        format!("Array<{}>", T::ts_type())
+    }
+}
+impl<T: TsType> TsType for Vec<T> {
+    fn ts_type() -> String {
+        <[T]>::ts_type()
+    }
+}
+impl<T: TsType> TsType for VecDeque<T> {
+    fn ts_type() -> String {
+        <[T]>::ts_type()
+    }
+}
+impl<T: TsType> TsType for HashSet<T> {
+    fn ts_type() -> String {
+        <[T]>::ts_type()
+    }
+}
+impl<T: TsType> TsType for BTreeSet<T> {
+    fn ts_type() -> String {
+        <[T]>::ts_type()
+    }
+}
+impl<K, V: TsType> TsType for HashMap<K, V> {
+    fn ts_type() -> String {
+        format!("Record<string, {}>", V::ts_type())
+    }
+}
+impl<K, V: TsType> TsType for BTreeMap<K, V> {
+    fn ts_type() -> String {
+        format!("Record<string, {}>", V::ts_type())
+    }
+}
+impl<T: TsType + ?Sized> TsType for Box<T> {
+    fn ts_type() -> String {
+        T::ts_type()
+    }
+}
+impl<T: TsType + ?Sized> TsType for Rc<T> {
+    fn ts_type() -> String {
+        T::ts_type()
+    }
+}
+impl<T: TsType + ?Sized> TsType for Arc<T> {
+    fn ts_type() -> String {
+        T::ts_type()
+    }
+}
+impl<T: TsType + ?Sized> TsType for &T {
+    fn ts_type() -> String {
+        T::ts_type()
+    }
+}
+
+impl TsArgs for () {
+    fn ts_args() -> String {
+        String::new()
+    }
+}
+
+macro_rules! ts_tuple {
+    ($($t:ident $i:tt),+) => {
+        impl<$($t: TsType),+> TsType for ($($t,)+) {
+            fn ts_type() -> String {
+                let types: &[String] = &[$($t::ts_type()),+];
+                format!("[{}]", types.join(", "))
+            }
+        }
+        impl<$($t: TsType),+> TsArgs for ($($t,)+) {
+            fn ts_args() -> String {
+                let args: &[String] = &[$(format!("arg{}: {}", $i, $t::ts_type())),+];
+                args.join(", ")
+            }
+        }
+    };
+}
+ts_tuple!(A 0);
+ts_tuple!(A 0, B 1);
+ts_tuple!(A 0, B 1, C 2);
+ts_tuple!(A 0, B 1, C 2, D 3);
+ts_tuple!(A 0, B 1, C 2, D 3, E 4);
+ts_tuple!(A 0, B 1, C 2, D 3, E 4, F 5);
+ts_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+ts_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+
+/// Implements `TsType` for a type of your own, either from its serialized fields or from a literal TypeScript type.
+///
+/// ```ignore
+/// ts_interface!(Point { x: f64, y: f64, label: Option<String> });
+/// ts_interface!(Mode = "'light' | 'dark'");
+/// ```
+///
+/// Fields are written as given, so list them under the names serde uses.
+#[macro_export]
+macro_rules! ts_interface {
+    ($t:ty { $($field:ident : $ft:ty),* $(,)? }) => {
+        impl $crate::TsType for $t {
+            fn ts_type() -> String {
+                let fields: &[String] = &[$(format!("{}: {}", stringify!($field), <$ft as $crate::TsType>::ts_type())),*];
+                format!("{{ {} }}", fields.join("; "))
+            }
+        }
+    };
+    ($t:ty = $ts:expr) => {
+        impl $crate::TsType for $t {
+            fn ts_type() -> String {
+                $ts.into()
+            }
+        }
+    };
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TsSignature {
+    pub args: String,
+    pub result: String,
+}
+
+impl TsSignature {
+    pub fn of<A, R>() -> Self {
+        TsSignature { args: A::ts_args(), result: R::ts_type() }
+    }
+    pub fn untyped() -> Self {
+        TsSignature { args: "...args: any[]".into(), result: "any".into() }
+    }
+}
+
+enum Node<'a> {
+    Method(&'a TsSignature),
+    Object(BTreeMap<&'a str, Node<'a>>),
+}
+
+fn insert<'a>(tree: &mut BTreeMap<&'a str, Node<'a>>, path: &[&'a str], signature: &'a TsSignature) {
+    match path {
+        [] => {}
+        [name] => {
+            tree.insert(name, Node::Method(signature));
+        }
+        [name, rest @ ..] => {
+            let node = tree.entry(name).or_insert_with(|| Node::Object(BTreeMap::new()));
+            if let Node::Method(_) = node {
+                *node = Node::Object(BTreeMap::new());
+            }
+            if let Node::Object(children) = node {
+                insert(children, rest, signature);
+            }
+        }
+    }
+}
+
+fn property(name: &str) -> String {
+    let mut chars = name.chars();
+    let identifier = chars.next().map(|c| c.is_alphabetic() || c == '_' || c == '$').unwrap_or(false) && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$');
+    if identifier {
+        name.into()
+    } else {
+        Value::from(name).to_string()
+    }
+}
+
+fn write_tree(out: &mut String, tree: &BTreeMap<&str, Node>, indent: usize) {
+    for (name, node) in tree {
+        let _ = match node {
+            Node::Method(signature) => writeln!(out, "{:indent$}{}({}): Promise<{}>;", "", property(name), signature.args, signature.result, indent = indent),
+            Node::Object(children) => {
+                let _ = writeln!(out, "{:indent$}{}: {{", "", property(name), indent = indent);
+                write_tree(out, children, indent + 4);
+                writeln!(out, "{:indent$}}};", "", indent = indent)
+            }
+        };
+    }
+}
+
+/// Renders a `.d.ts` module declaring every binding in `signatures` on the global `Window`, and the JSON-RPC methods in `rpc_methods`.
+pub fn declarations<'a, I: IntoIterator<Item = (&'a String, &'a TsSignature)>>(signatures: I, rpc_methods: &BTreeMap<String, TsSignature>) -> String {
+    let mut tree = BTreeMap::new();
+    for (name, signature) in signatures {
+        let path = name.split('.').collect::<Vec<_>>();
+        if path[0] == NAMESPACE_ROOT && path.len() > 1 {
+            insert(&mut tree, &path, signature);
+        } else {
+            insert(&mut tree, &[name.as_str()], signature);
+        }
+    }
+    let mut out = String::new();
+    out.push_str("// Generated by plygui-webview. Do not edit.\n\n");
+    out.push_str("export interface PlyguiRuntime {\n");
+    out.push_str("    on(name: string, handler: (payload: any) => void): (payload: any) => void;\n");
+    out.push_str("    off(name: string, handler?: (payload: any) => void): void;\n");
+    out.push_str("    store<T = any>(name: string): PlyguiStore<T>;\n");
+    out.push_str("    call(name: string, args?: any[], options?: { signal?: AbortSignal }): Promise<any>;\n");
+    out.push_str("    jsonrpc(message: any): Promise<any>;\n");
+    out.push_str("}\n\n");
+    out.push_str("export interface PlyguiStore<T = any> {\n");
+    out.push_str("    get(): T | undefined;\n");
+    out.push_str("    version(): number;\n");
+    out.push_str("    ready(): Promise<T>;\n");
+    out.push_str("    set(value: T): Promise<T>;\n");
+    out.push_str("    update(updater: (value: T) => T): Promise<T>;\n");
+    out.push_str("    subscribe(subscriber: (value: T, version: number) => void): () => void;\n");
+    out.push_str("}\n\n");
+    out.push_str("export interface PlyguiStream<T> extends AsyncIterableIterator<T> {\n");
+    out.push_str("    cancel(): Promise<IteratorResult<T>>;\n");
+    out.push_str("    readable?(): ReadableStream<T>;\n");
+    out.push_str("}\n\n");
+    out.push_str("export interface PlyguiRpcMethods {\n");
+    for (name, signature) in rpc_methods {
+        let _ = writeln!(out, "    {}({}): {};", property(name), signature.args, signature.result);
+    }
+    out.push_str("}\n\n");
+    out.push_str("declare global {\n");
+    out.push_str("    interface Window {\n");
+    out.push_str("        plygui: PlyguiRuntime;\n");
+    write_tree(&mut out, &tree, 8);
+    out.push_str("    }\n");
+    out.push_str("}\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Point;
+    crate::ts_interface!(Point { x: f64, label: Option<String> });
+
+    #[test]
+    fn types() {
+        assert_eq!(<(String, Option<Vec<i32>>)>::ts_args(), "arg0: string, arg1: Array<number> | null");
+        assert_eq!(<HashMap<String, f64>>::ts_type(), "Record<string, number>");
+        assert_eq!(Point::ts_type(), "{ x: number; label: string | null }");
+        assert_eq!(<Vec<Point>>::ts_type(), "Array<{ x: number; label: string | null }>");
+    }
+
+    #[test]
+    fn declarations_nest_namespaces() {
+        let mut signatures = BTreeMap::new();
+        signatures.insert("hello".to_string(), TsSignature::of::<(String,), String>());
+        signatures.insert(format!("{}.fs.read", NAMESPACE_ROOT), TsSignature::of::<(String,), Vec<u8>>());
+        signatures.insert("weird-name".to_string(), TsSignature::untyped());
+        let mut rpc_methods = BTreeMap::new();
+        rpc_methods.insert("add".to_string(), TsSignature::of::<(f64, f64), f64>());
+        let out = declarations(&signatures, &rpc_methods);
+        assert!(out.contains("        hello(arg0: string): Promise<string>;\n"), "{}", out);
+        assert!(out.contains("        api: {\n            fs: {\n                read(arg0: string): Promise<Array<number>>;\n            };\n        };\n"), "{}", out);
+        assert!(out.contains("        \"weird-name\"(...args: any[]): Promise<any>;\n"), "{}", out);
+        assert!(out.contains("export interface PlyguiRpcMethods {\n    add(arg0: number, arg1: number): number;\n}\n"), "{}", out);
+    }
+}
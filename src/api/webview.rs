@@ -3,10 +3,31 @@ use plygui_api::{
     sdk::{AControl, ControlInner, HasInner, AMember, Abstract, MemberBase, ControlBase},
 };
 
+use super::assets::{AssetServer, DirectoryAssets};
+use super::bridge::{self, WebviewBridge, Responder};
+use super::calls;
+use super::eval::{self, JsError, JsFuture};
+use super::hosts;
+use super::loopback::LoopbackServer;
+use super::middleware::BindMiddleware;
+use super::origin::Caller;
+use super::namespace::{WebviewApi, WebviewNamespace};
+use super::registry::BindingInfo;
+use super::rpc::{self, JsonRpc};
+use super::scheme::SchemeHandler;
+use super::store::{self, SharedStore};
+use super::stream::{self, StreamSink};
+use super::typed::{self, BindError, IntoBindError};
+use super::typescript::TsSignature;
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
 use webview_sys;
 use std::borrow::Cow;
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
 use std::fmt::Debug;
+use std::path::Path;
+use std::time::Duration;
 
 pub enum WebviewError {
 	MissingDependency,
@@ -52,6 +73,8 @@ define! {
         outer: {
             fn navigate(&mut self, url: Cow<str>) -> Result<(), WebviewError>;
             fn set_html(&mut self, html: Cow<str>) -> Result<(), WebviewError>;
+            fn set_html_with_base(&mut self, html: Cow<str>, base_url: Cow<str>) -> Result<(), WebviewError>;
+            fn load_data(&mut self, data: Cow<[u8]>, mime_type: Cow<str>, encoding: Option<Cow<str>>, base_url: Option<Cow<str>>) -> Result<(), WebviewError>;
             fn init(&mut self, js: Cow<str>) -> Result<(), WebviewError>;
 			fn eval(&mut self, js: Cow<str>) -> Result<(), WebviewError>;
 			fn url(&self) -> Result<Cow<str>, WebviewError>;
@@ -64,6 +87,8 @@ define! {
         inner: {
             fn navigate(&mut self, member: &mut MemberBase, control: &mut ControlBase, url: Cow<str>) -> Result<(), WebviewError>;
             fn set_html(&mut self, member: &mut MemberBase, control: &mut ControlBase, html: Cow<str>) -> Result<(), WebviewError>;
+            fn set_html_with_base(&mut self, member: &mut MemberBase, control: &mut ControlBase, html: Cow<str>, base_url: Cow<str>) -> Result<(), WebviewError>;
+            fn load_data(&mut self, member: &mut MemberBase, control: &mut ControlBase, data: Cow<[u8]>, mime_type: Cow<str>, encoding: Option<Cow<str>>, base_url: Option<Cow<str>>) -> Result<(), WebviewError>;
             fn init(&mut self, member: &mut MemberBase, control: &mut ControlBase, js: Cow<str>) -> Result<(), WebviewError>;
 			fn eval(&mut self, member: &mut MemberBase, control: &mut ControlBase, js: Cow<str>) -> Result<(), WebviewError>;
 			fn url(&self, member: &MemberBase, control: &ControlBase) -> Result<Cow<str>, WebviewError>;
@@ -88,6 +113,12 @@ impl<II: WebviewInner, T: HasInner<I = II> + Abstract + 'static> WebviewInner fo
 	default fn set_html(&mut self, member: &mut MemberBase, control: &mut ControlBase, html: Cow<str>) -> Result<(), WebviewError> {
 		self.inner_mut().set_html(member, control, html)
 	}
+	default fn set_html_with_base(&mut self, member: &mut MemberBase, control: &mut ControlBase, html: Cow<str>, base_url: Cow<str>) -> Result<(), WebviewError> {
+		self.inner_mut().set_html_with_base(member, control, html, base_url)
+	}
+	default fn load_data(&mut self, member: &mut MemberBase, control: &mut ControlBase, data: Cow<[u8]>, mime_type: Cow<str>, encoding: Option<Cow<str>>, base_url: Option<Cow<str>>) -> Result<(), WebviewError> {
+		self.inner_mut().load_data(member, control, data, mime_type, encoding, base_url)
+	}
 	default fn init(&mut self, member: &mut MemberBase, control: &mut ControlBase, js: Cow<str>) -> Result<(), WebviewError> {
 		self.inner_mut().init(member, control, js)
 	}
@@ -120,6 +151,12 @@ impl<T: WebviewInner> Webview for AMember<AControl<AWebview<T>>> {
 	default fn set_html(&mut self, html: Cow<str>) -> Result<(), WebviewError> {
 		self.inner.inner.inner.set_html(&mut self.base, &mut self.inner.base, html)
 	}
+	default fn set_html_with_base(&mut self, html: Cow<str>, base_url: Cow<str>) -> Result<(), WebviewError> {
+		self.inner.inner.inner.set_html_with_base(&mut self.base, &mut self.inner.base, html, base_url)
+	}
+	default fn load_data(&mut self, data: Cow<[u8]>, mime_type: Cow<str>, encoding: Option<Cow<str>>, base_url: Option<Cow<str>>) -> Result<(), WebviewError> {
+		self.inner.inner.inner.load_data(&mut self.base, &mut self.inner.base, data, mime_type, encoding, base_url)
+	}
 	default fn init(&mut self, js: Cow<str>) -> Result<(), WebviewError> {
 		self.inner.inner.inner.init(&mut self.base, &mut self.inner.base, js)
 	}
@@ -155,10 +192,194 @@ impl<T: WebviewInner> NewWebview for AMember<AControl<AWebview<T>>> {
 }
 pub trait WebviewBindContext: Send + Sized {}
 
+impl WebviewBindContext for () {}
+
 pub trait WebviewExt: Webview {
+	/// `context` stays write-locked while `callback` runs; re-entrant calls are rejected, see `context::enter`.
 	fn bind<C, F>(&mut self, name: Cow<str>, context: Arc<RwLock<C>>, callback: F) -> Result<(), WebviewError> where C: WebviewBindContext, F: FnMut(&mut Self, &str, &str, &mut C);
 	fn unbind(&mut self, name: Cow<str>) -> Result<(), WebviewError>;
 	fn return_(&mut self, id: Cow<str>, status: i32, result: Cow<str>) -> Result<(), WebviewError>;
+	fn bridge(&self) -> WebviewBridge;
+	/// Answers every request the page makes to `scheme://` with `handler`, replacing the handler registered before.
+	/// Fails with `Duplicate` where views share schemes (QtWebEngine profiles) and another view already handles `scheme`.
+	fn register_scheme(&mut self, scheme: Cow<str>, handler: SchemeHandler) -> Result<(), WebviewError>;
+	fn unregister_scheme(&mut self, scheme: Cow<str>) -> Result<(), WebviewError>;
+	
+	fn install_runtime(&mut self) -> Result<(), WebviewError> where Self: Sized {
+		let bridge = self.bridge();
+		if !bridge.is_runtime_installed() {
+			let installed = calls::install(self).and_then(|_| self.init(Cow::Borrowed(bridge::RUNTIME))).and_then(|_| self.eval(Cow::Borrowed(bridge::RUNTIME)));
+			if let Err(e) = installed {
+				// The runtime script ignores a second copy, so only the helper bindings stand in the way of a retry.
+				calls::uninstall(self);
+				return Err(e);
+			}
+			bridge.mark_runtime_installed();
+		}
+		Ok(())
+	}
+	fn bindings(&self) -> Vec<BindingInfo> {
+		self.bridge().bindings()
+	}
+	fn is_bound(&self, name: &str) -> bool {
+		self.bridge().is_bound(name)
+	}
+	fn binding_info(&self, name: &str) -> Option<BindingInfo> {
+		self.bridge().binding(name)
+	}
+	/// Declares the TypeScript signature of a binding made with `bind` or `bind_fn`, which have no types to infer it from.
+	fn describe_binding(&mut self, name: &str, signature: TsSignature) -> Result<(), WebviewError> where Self: Sized {
+		let bridge = self.bridge();
+		if !bridge.is_bound(name) {
+			return Err(WebviewError::NotFound);
+		}
+		bridge.describe(name, signature);
+		Ok(())
+	}
+	fn add_middleware<M: BindMiddleware + 'static>(&mut self, middleware: M) where Self: Sized {
+		self.bridge().add_middleware(middleware)
+	}
+	fn set_default_origins<S: AsRef<str>>(&mut self, patterns: Option<&[S]>) -> Result<(), WebviewError> where Self: Sized {
+		self.bridge().set_default_origins(patterns)
+	}
+	fn set_binding_origins<S: AsRef<str>>(&mut self, name: &str, patterns: Option<&[S]>) -> Result<(), WebviewError> where Self: Sized {
+		self.bridge().set_binding_origins(name, patterns)
+	}
+	fn set_default_timeout(&mut self, timeout: Option<Duration>) where Self: Sized {
+		self.bridge().set_default_timeout(timeout)
+	}
+	fn set_binding_timeout(&mut self, name: &str, timeout: Option<Duration>) where Self: Sized {
+		self.bridge().set_binding_timeout(name, timeout)
+	}
+	fn emit<P: Serialize + ?Sized>(&mut self, name: &str, payload: &P) -> Result<(), WebviewError> where Self: Sized {
+		self.install_runtime()?;
+		let js = bridge::emit_script(name, payload)?;
+		self.eval(Cow::Owned(js))
+	}
+	/// Runs `js` through `eval`, which a CSP without `'unsafe-eval'` blocks; `call_js` does not need it.
+	/// If the page goes away before answering, `callback` gets an `AbortError` once the next document loads.
+	fn eval_with_result<F>(&mut self, js: Cow<str>, callback: F) -> Result<(), WebviewError> 
+			where Self: Sized + 'static, F: FnOnce(&mut Self, Result<Value, JsError>) + Send + 'static {
+		eval::eval_with_result(self, &js, callback)
+	}
+	fn eval_async(&mut self, js: Cow<str>) -> Result<JsFuture<Value>, WebviewError> where Self: Sized + 'static {
+		let (future, completer) = JsFuture::new();
+		self.eval_with_result(js, move |_, result| completer.complete(result))?;
+		Ok(future)
+	}
+	fn call_js<A, R, F>(&mut self, path: &str, args: &A, callback: F) -> Result<(), WebviewError> 
+			where Self: Sized + 'static, A: Serialize, R: DeserializeOwned, F: FnOnce(&mut Self, Result<R, JsError>) + Send + 'static {
+		let js = eval::call_script(path, args)?;
+		eval::evaluate(self, &js, move |this, result| {
+			let result = result.and_then(|value| serde_json::from_value(value).map_err(|e| JsError::new("TypeError", e.to_string())));
+			callback(this, result)
+		})
+	}
+	fn call_js_async<A, R>(&mut self, path: &str, args: &A) -> Result<JsFuture<R>, WebviewError> 
+			where Self: Sized + 'static, A: Serialize, R: DeserializeOwned + Send + 'static {
+		let (future, completer) = JsFuture::new();
+		self.call_js(path, args, move |_, result| completer.complete(result))?;
+		Ok(future)
+	}
+	fn bind_jsonrpc(&mut self, rpc: JsonRpc<Self>) -> Result<(), WebviewError> where Self: Sized + 'static {
+		rpc::bind(self, rpc)
+	}
+	fn bind_namespace<C>(&mut self, namespace: WebviewNamespace<Self, C>, context: Arc<RwLock<C>>) -> Result<(), WebviewError> 
+			where Self: Sized + 'static, C: WebviewBindContext + 'static {
+		namespace.bind(self, context)
+	}
+	fn bind_api<A>(&mut self, api: Arc<RwLock<A>>) -> Result<(), WebviewError> where Self: Sized + 'static, A: WebviewApi<Self> + 'static {
+		let name = api.read().map_err(|_| WebviewError::InvalidState)?.namespace().into_owned();
+		self.bind_namespace(A::methods(WebviewNamespace::new(name)), api)
+	}
+	/// Binds a plain closure. It runs on the UI thread only, so it may capture `Rc`/`RefCell` state, and it shares no lock with other bindings.
+	fn bind_fn<F>(&mut self, name: Cow<str>, mut callback: F) -> Result<(), WebviewError> where Self: Sized, F: FnMut(&mut Self, &str, &str) {
+		self.bind(name, Arc::new(RwLock::new(())), move |this, id, req, _| callback(this, id, req))
+	}
+	fn bind_typed_fn<A, R, E, F>(&mut self, name: Cow<str>, mut callback: F) -> Result<(), WebviewError> 
+			where Self: Sized, A: DeserializeOwned, R: Serialize, E: IntoBindError, F: FnMut(&mut Self, A) -> Result<R, E> {
+		self.bind_typed(name, Arc::new(RwLock::new(())), move |this, args, _, _: &mut ()| callback(this, args))
+	}
+	/// Binds a callback taking the deserialized arguments and the frame that called; its result settles the JS promise.
+	fn bind_typed<C, A, R, E, F>(&mut self, name: Cow<str>, context: Arc<RwLock<C>>, mut callback: F) -> Result<(), WebviewError> 
+			where Self: Sized, C: WebviewBindContext, A: DeserializeOwned, R: Serialize, E: IntoBindError, F: FnMut(&mut Self, A, &Caller, &mut C) -> Result<R, E> {
+		self.install_runtime()?;
+		let bridge = self.bridge();
+		let signature = TsSignature::of::<A, R>();
+		self.bind(name.clone(), context, move |this, id, req, context| {
+			let caller = this.bridge().caller(id).unwrap_or_else(|| Caller::new(String::new()));
+			let (status, result) = typed::invoke(req, |args| callback(this, args, &caller, context));
+			let _ = this.return_(Cow::Borrowed(id), status, Cow::Owned(result));
+		})?;
+		bridge.describe(&name, signature);
+		typed::expose(self, &name)
+	}
+	fn register_store<T>(&mut self, store: &SharedStore<T>) -> Result<(), WebviewError> 
+			where Self: Sized, T: Clone + Serialize + DeserializeOwned + Send + Sync + 'static {
+		store::register(self, store)
+	}
+	fn unregister_store(&mut self, name: &str) -> Result<(), WebviewError> where Self: Sized {
+		store::unregister(self, name)
+	}
+	/// Serves `server` under `scheme://`, e.g. `app://ui/index.html`.
+	fn serve_assets(&mut self, scheme: &str, server: AssetServer) -> Result<(), WebviewError> where Self: Sized {
+		self.register_scheme(Cow::Borrowed(scheme), server.handler())
+	}
+	/// Serves `server` as the virtual host `host` and returns its base URL, e.g. `plygui://app.local/`.
+	/// The URL is not `https://`, see `hosts::HOST_SCHEME` for what that means for the page's origin.
+	/// Every host lives under that one scheme, so on Qt only one view per profile can serve hosts, see `register_scheme`.
+	fn serve_host(&mut self, host: &str, server: AssetServer) -> Result<String, WebviewError> where Self: Sized {
+		hosts::serve(self, host, server)
+	}
+	/// Maps the virtual host `host` to a directory on disk, see `serve_host`.
+	fn serve_directory<P: AsRef<Path>>(&mut self, host: &str, path: P) -> Result<String, WebviewError> where Self: Sized {
+		let assets = DirectoryAssets::new(path).map_err(|_| WebviewError::NotFound)?;
+		hosts::serve(self, host, AssetServer::new(assets))
+	}
+	fn unserve_host(&mut self, host: &str) -> Result<(), WebviewError> where Self: Sized {
+		hosts::unserve(self, host)
+	}
+	/// Serves `server` over HTTP on 127.0.0.1 for as long as the control lives, replacing a previous loopback server,
+	/// and navigates to it. Returns the base URL, which carries the session token.
+	fn serve_loopback(&mut self, server: AssetServer) -> Result<String, WebviewError> where Self: Sized {
+		let loopback = LoopbackServer::start(server)?;
+		let url = loopback.url();
+		let (slot, _) = self.bridge().extension::<Mutex<Option<LoopbackServer>>, _>(|| Mutex::new(None));
+		let previous = slot.lock().unwrap().replace(loopback);
+		drop(previous);
+		self.navigate(Cow::Borrowed(&url))?;
+		Ok(url)
+	}
+	/// Serves `server` as the virtual host `host` and navigates to it, falling back to `serve_loopback` on backends without custom schemes.
+	fn serve_host_or_loopback(&mut self, host: &str, server: AssetServer) -> Result<String, WebviewError> where Self: Sized {
+		match hosts::serve(self, host, server.clone()) {
+			Ok(url) => {
+				self.navigate(Cow::Borrowed(&url))?;
+				Ok(url)
+			}
+			Err(WebviewError::MissingDependency) => self.serve_loopback(server),
+			Err(e) => Err(e),
+		}
+	}
+	fn bind_stream<C, A, T, F>(&mut self, name: Cow<str>, context: Arc<RwLock<C>>, callback: F) -> Result<(), WebviewError> 
+			where Self: Sized, C: WebviewBindContext, A: DeserializeOwned, T: Serialize, F: FnMut(&mut Self, A, StreamSink<T>, &mut C) {
+		stream::bind(self, name, context, callback)
+	}
+	fn bind_deferred<C, A, F>(&mut self, name: Cow<str>, context: Arc<RwLock<C>>, mut callback: F) -> Result<(), WebviewError> 
+			where Self: Sized, C: WebviewBindContext, A: DeserializeOwned, F: FnMut(&mut Self, A, Responder, &mut C) {
+		self.install_runtime()?;
+		let bridge = self.bridge();
+		let signature = TsSignature::of::<A, Value>();
+		self.bind(name.clone(), context, move |this, id, req, context| {
+			let responder = Responder::new(this.bridge(), id);
+			match typed::parse_args::<A>(req) {
+				Ok(args) => callback(this, args, responder, context),
+				Err(e) => { let _ = responder.reject(BindError::invalid_arguments(e)); }
+			}
+		})?;
+		bridge.describe(&name, signature);
+		typed::expose(self, &name)
+	}
 }
 pub trait WebviewExtInner: WebviewInner {
 	type W: WebviewExt;
@@ -166,6 +387,9 @@ pub trait WebviewExtInner: WebviewInner {
 		where C: WebviewBindContext, F: FnMut(&mut Self::W, &str, &str, &mut C);
 	fn unbind(&mut self, member: &mut MemberBase, control: &mut ControlBase, name: Cow<str>) -> Result<(), WebviewError>;
 	fn return_(&mut self, member: &mut MemberBase, control: &mut ControlBase, id: Cow<str>, status: i32, result: Cow<str>) -> Result<(), WebviewError>;
+	fn bridge(&self, member: &MemberBase, control: &ControlBase) -> WebviewBridge;
+	fn register_scheme(&mut self, member: &mut MemberBase, control: &mut ControlBase, scheme: Cow<str>, handler: SchemeHandler) -> Result<(), WebviewError>;
+	fn unregister_scheme(&mut self, member: &mut MemberBase, control: &mut ControlBase, scheme: Cow<str>) -> Result<(), WebviewError>;
 }
 impl<WW: WebviewExt, II: WebviewExtInner<W=WW>, T: HasInner<I = II> + Abstract + 'static> WebviewExtInner for T {
 	type W = WW;
@@ -179,6 +403,15 @@ impl<WW: WebviewExt, II: WebviewExtInner<W=WW>, T: HasInner<I = II> + Abstract +
 	default fn return_(&mut self, member: &mut MemberBase, control: &mut ControlBase, id: Cow<str>, status: i32, result: Cow<str>) -> Result<(), WebviewError> {
 		self.inner_mut().return_(member, control, id, status, result)
 	}
+	default fn bridge(&self, member: &MemberBase, control: &ControlBase) -> WebviewBridge {
+		self.inner().bridge(member, control)
+	}
+	default fn register_scheme(&mut self, member: &mut MemberBase, control: &mut ControlBase, scheme: Cow<str>, handler: SchemeHandler) -> Result<(), WebviewError> {
+		self.inner_mut().register_scheme(member, control, scheme, handler)
+	}
+	default fn unregister_scheme(&mut self, member: &mut MemberBase, control: &mut ControlBase, scheme: Cow<str>) -> Result<(), WebviewError> {
+		self.inner_mut().unregister_scheme(member, control, scheme)
+	}
 }
 impl<T: WebviewExtInner<W=Self>> WebviewExt for AMember<AControl<AWebview<T>>> {
     default fn bind<C, F>(&mut self, name: Cow<str>, context: Arc<RwLock<C>>, callback: F) -> Result<(), WebviewError> where C: WebviewBindContext, F: FnMut(&mut Self, &str, &str, &mut C) {
@@ -190,4 +423,13 @@ impl<T: WebviewExtInner<W=Self>> WebviewExt for AMember<AControl<AWebview<T>>> {
 	default fn return_(&mut self, id: Cow<str>, status: i32, result: Cow<str>) -> Result<(), WebviewError> {
 		self.inner.inner.inner.return_(&mut self.base, &mut self.inner.base, id, status, result)
 	}
+	default fn bridge(&self) -> WebviewBridge {
+		self.inner.inner.inner.bridge(&self.base, &self.inner.base)
+	}
+	default fn register_scheme(&mut self, scheme: Cow<str>, handler: SchemeHandler) -> Result<(), WebviewError> {
+		self.inner.inner.inner.register_scheme(&mut self.base, &mut self.inner.base, scheme, handler)
+	}
+	default fn unregister_scheme(&mut self, scheme: Cow<str>) -> Result<(), WebviewError> {
+		self.inner.inner.inner.unregister_scheme(&mut self.base, &mut self.inner.base, scheme)
+	}
 }
\ No newline at end of file
@@ -6,16 +6,90 @@ use webview_sys;
 
 use std::str;
 use std::ffi::CStr;
-use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
+use std::collections::{HashMap, HashSet};
+use std::os::raw::c_uint;
+use std::ptr;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, RwLock};
 
 pub type Webview = AMember<AControl<AWebview<GtkWebview>>>;
 
+/// Parts of WebKitGTK (2.36+), libsoup and GIO that `webview_sys` does not wrap.
+mod webkit {
+    use std::os::raw::{c_char, c_int, c_uint, c_void};
+
+    pub type SchemeRequestCallback = Option<unsafe extern "C" fn(request: *mut c_void, user_data: *mut c_void)>;
+    pub type DestroyNotify = Option<unsafe extern "C" fn(data: *mut c_void)>;
+    pub type HeadersForeachFunc = Option<unsafe extern "C" fn(name: *const c_char, value: *const c_char, user_data: *mut c_void)>;
+
+    pub const SOUP_MESSAGE_HEADERS_RESPONSE: c_int = 1;
+
+    extern "C" {
+        pub fn webkit_web_view_load_html(web_view: *mut c_void, content: *const c_char, base_uri: *const c_char);
+        pub fn webkit_web_view_load_bytes(web_view: *mut c_void, bytes: *mut c_void, mime_type: *const c_char, encoding: *const c_char, base_uri: *const c_char);
+        pub fn webkit_web_view_get_context(web_view: *mut c_void) -> *mut c_void;
+        pub fn webkit_web_context_register_uri_scheme(context: *mut c_void, scheme: *const c_char, callback: SchemeRequestCallback, user_data: *mut c_void, destroy: DestroyNotify);
+        pub fn webkit_web_context_get_security_manager(context: *mut c_void) -> *mut c_void;
+        pub fn webkit_security_manager_register_uri_scheme_as_secure(manager: *mut c_void, scheme: *const c_char);
+        pub fn webkit_security_manager_register_uri_scheme_as_cors_enabled(manager: *mut c_void, scheme: *const c_char);
+        pub fn webkit_uri_scheme_request_get_uri(request: *mut c_void) -> *const c_char;
+        pub fn webkit_uri_scheme_request_get_web_view(request: *mut c_void) -> *mut c_void;
+        pub fn webkit_uri_scheme_request_get_http_method(request: *mut c_void) -> *const c_char;
+        pub fn webkit_uri_scheme_request_get_http_headers(request: *mut c_void) -> *mut c_void;
+        pub fn webkit_uri_scheme_request_finish_with_response(request: *mut c_void, response: *mut c_void);
+        pub fn webkit_uri_scheme_response_new(stream: *mut c_void, length: i64) -> *mut c_void;
+        pub fn webkit_uri_scheme_response_set_status(response: *mut c_void, status: c_uint, reason: *const c_char);
+        pub fn webkit_uri_scheme_response_set_content_type(response: *mut c_void, content_type: *const c_char);
+        pub fn webkit_uri_scheme_response_set_http_headers(response: *mut c_void, headers: *mut c_void);
+        pub fn soup_message_headers_new(kind: c_int) -> *mut c_void;
+        pub fn soup_message_headers_append(headers: *mut c_void, name: *const c_char, value: *const c_char);
+        pub fn soup_message_headers_foreach(headers: *mut c_void, func: HeadersForeachFunc, user_data: *mut c_void);
+        pub fn g_bytes_new(data: *const c_void, size: usize) -> *mut c_void;
+        pub fn g_bytes_unref(bytes: *mut c_void);
+        pub fn g_memory_input_stream_new_from_bytes(bytes: *mut c_void) -> *mut c_void;
+        pub fn g_object_unref(object: *mut c_void);
+    }
+}
+
+/// WebKit registers a scheme once per web context, so the handlers of all webviews sharing it live here.
+#[derive(Default)]
+struct Schemes {
+    registered: HashSet<(usize, String)>,
+    handlers: HashMap<(usize, String), SchemeHandler>,
+}
+
+lazy_static! {
+    static ref SCHEMES: Mutex<Schemes> = Mutex::new(Schemes::default());
+}
+
+struct WebviewBinding {
+    name: String,
+    object: *mut GObject,
+    context: *mut c_void,
+    callback: *mut c_void,
+    bridge: WebviewBridge,
+    free: unsafe fn(*mut c_void, *mut c_void),
+}
+impl Drop for WebviewBinding {
+    fn drop(&mut self) {
+        unsafe {
+            use crate::plygui_gtk::glib::translate::FromGlibPtrFull;
+            (self.free)(self.context, self.callback);
+            let _ = Object::from_glib_full(self.object);
+        }
+    }
+}
+unsafe fn free_binding<F, C>(context: *mut c_void, callback: *mut c_void) {
+    let _ = Arc::from_raw(context as *const RwLock<C>);
+    let _ = Box::from_raw(callback as *mut F);
+}
+
 #[repr(C)]
 pub struct GtkWebview {
     base: GtkControlBase<Webview>,
     webview_wrapper: *mut c_void,
-    bindings: HashMap<String, Box<[*mut c_void; 3]>>,
+    bindings: HashMap<String, Box<WebviewBinding>>,
+    bridge: WebviewBridge,
 }
 
 impl<O: crate::Webview> NewWebviewInner<O> for GtkWebview {
@@ -29,6 +103,7 @@ impl<O: crate::Webview> NewWebviewInner<O> for GtkWebview {
             }),
             webview_wrapper,
             bindings: HashMap::new(),
+            bridge: WebviewBridge::new(webview_wrapper),
         };
         {
             let ptr = u as *mut _ as *mut c_void;
@@ -57,15 +132,46 @@ impl WebviewInner for GtkWebview {
         unsafe {
             let c_url = CString::new(&*url).map_err(|_| WebviewError::InvalidArgument)?;
             let err_code = webview_sys::webview_navigate(self.webview_wrapper, c_url.as_ptr());
-            WebviewError::from_native(err_code)
+            WebviewError::from_native(err_code)?;
         }
+        self.bridge.abandon_calls(None);
+        Ok(())
     }
     fn set_html(&mut self, _member: &mut MemberBase, _control: &mut ControlBase, html: Cow<str>) -> Result<(), WebviewError> {
         unsafe {
             let c_html = CString::new(&*html).map_err(|_| WebviewError::InvalidArgument)?;
             let err_code = webview_sys::webview_set_html(self.webview_wrapper, c_html.as_ptr());
-            WebviewError::from_native(err_code)
+            WebviewError::from_native(err_code)?;
         }
+        self.bridge.abandon_calls(None);
+        Ok(())
+    }
+    fn set_html_with_base(&mut self, _member: &mut MemberBase, _control: &mut ControlBase, html: Cow<str>, base_url: Cow<str>) -> Result<(), WebviewError> {
+        let c_html = CString::new(&*html).map_err(|_| WebviewError::InvalidArgument)?;
+        let c_base_url = CString::new(&*base_url).map_err(|_| WebviewError::InvalidArgument)?;
+        unsafe {
+            webkit::webkit_web_view_load_html(self.web_view(), c_html.as_ptr(), c_base_url.as_ptr());
+        }
+        self.bridge.abandon_calls(None);
+        Ok(())
+    }
+    fn load_data(&mut self, _member: &mut MemberBase, _control: &mut ControlBase, data: Cow<[u8]>, mime_type: Cow<str>, encoding: Option<Cow<str>>, base_url: Option<Cow<str>>) -> Result<(), WebviewError> {
+        let c_mime_type = CString::new(&*mime_type).map_err(|_| WebviewError::InvalidArgument)?;
+        let c_encoding = encoding.map(|encoding| CString::new(&*encoding)).transpose().map_err(|_| WebviewError::InvalidArgument)?;
+        let c_base_url = base_url.map(|base_url| CString::new(&*base_url)).transpose().map_err(|_| WebviewError::InvalidArgument)?;
+        unsafe {
+            let bytes = webkit::g_bytes_new(data.as_ptr() as *const c_void, data.len());
+            webkit::webkit_web_view_load_bytes(
+                self.web_view(),
+                bytes,
+                c_mime_type.as_ptr(),
+                c_encoding.as_ref().map_or(ptr::null(), |encoding| encoding.as_ptr()),
+                c_base_url.as_ref().map_or(ptr::null(), |base_url| base_url.as_ptr()),
+            );
+            webkit::g_bytes_unref(bytes);
+        }
+        self.bridge.abandon_calls(None);
+        Ok(())
     }
     fn init(&mut self, _member: &mut MemberBase, _control: &mut ControlBase, js: Cow<str>) -> Result<(), WebviewError> {
         unsafe {
@@ -131,14 +237,14 @@ impl WebviewExtInner for GtkWebview {
     fn bind<C, F>(&mut self, _member: &mut MemberBase, _control: &mut ControlBase, name: Cow<str>, context: Arc<RwLock<C>>, callback: F) -> Result<(), WebviewError> 
             where F: FnMut(&mut Self::W, &str, &str, &mut C), C: WebviewBindContext {
         let widget: Object = Object::from(self.base.widget.clone()).into();
-        let gobject: *mut GObject = widget.to_glib_full();
-        let context = Arc::into_raw(context) as *const _ as *mut c_void;
-        let callback = Box::new(callback);
-        let inner_context = Box::new([
-            gobject as *mut c_void, 
-            context, 
-            Box::into_raw(callback) as *mut c_void
-        ]);
+        let binding = Box::new(WebviewBinding {
+            name: name.to_string(),
+            object: widget.to_glib_full(),
+            context: Arc::into_raw(context) as *const _ as *mut c_void,
+            callback: Box::into_raw(Box::new(callback)) as *mut c_void,
+            bridge: self.bridge.clone(),
+            free: free_binding::<F, C>,
+        });
         extern "C" fn trampoline_webview_bind<
                 F: FnMut(&mut Webview, &str, &str, &mut CC),
                 CC: WebviewBindContext
@@ -148,66 +254,130 @@ impl WebviewExtInner for GtkWebview {
                 arg: *mut ::std::os::raw::c_void,
             ) {
                 unsafe {
-                    use crate::plygui_gtk::glib::translate::FromGlibPtrFull;
-                    let arg = &*(arg as *const [*mut c_void; 3]);
-                    let mut object = Object::from_glib_full(arg[0] as *mut GObject);
-                    let this: &mut Webview = cast_gobject_mut(&mut object).expect("Not a GTK Control");
-                    let id = CStr::from_ptr(id).to_str().expect("id is not a valid string");
-                    let req = CStr::from_ptr(req).to_str().expect("req is not a valid string");
-                    let context: &mut RwLock<CC> = mem::transmute(arg[1]);
-                    let callback: &mut F = mem::transmute(arg[2]);
-                    callback(this, id, req, &mut context.write().unwrap());
-                    mem::forget(object);
+                    use crate::plygui_gtk::glib::translate::FromGlibPtrNone;
+                    let binding = &*(arg as *const WebviewBinding);
+                    crate::api::bridge::dispatch(&binding.bridge, &binding.name, id, req, |id, req| {
+                        let mut object = Object::from_glib_none(binding.object);
+                        let this: &mut Webview = cast_gobject_mut(&mut object).expect("Not a GTK Control");
+                        let context = &*(binding.context as *const RwLock<CC>);
+                        let callback = &mut *(binding.callback as *mut F);
+                        crate::api::context::enter(context, |context| callback(this, id, req, context))
+                    });
                 }
             }
-            unsafe {
-                let c_name = CString::new(&*name).map_err(|_| WebviewError::InvalidArgument)?;
-                let err_code = webview_sys::webview_bind(
-                    self.webview_wrapper, 
-                    c_name.as_ptr(), 
-                    Some(trampoline_webview_bind::<F, C>), 
-                    inner_context.as_ptr() as *const _ as *mut c_void
-                );
-                let res = WebviewError::from_native(err_code);
-                match res {
-                    Err(_) => {
-                        self.bindings.remove(&name.to_string()).map(|binding| {
-                            let _ = Arc::from_raw(binding[1]);
-                            let _ = Box::from_raw(binding[2]);
-                        });                    
-                    },
-                    Ok(_) => {
-                        self.bindings.insert(name.to_string(), inner_context);
-                    }
-                }
-                res
+        unsafe {
+            let c_name = CString::new(&*name).map_err(|_| WebviewError::InvalidArgument)?;
+            let err_code = webview_sys::webview_bind(
+                self.webview_wrapper, 
+                c_name.as_ptr(), 
+                Some(trampoline_webview_bind::<F, C>), 
+                &*binding as *const _ as *mut c_void
+            );
+            let res = WebviewError::from_native(err_code);
+            if res.is_ok() {
+                self.bridge.register(&name);
+                self.bindings.insert(name.into_owned(), binding);
             }
+            res
+        }
     }
     fn unbind(&mut self, _member: &mut MemberBase, _control: &mut ControlBase, name: Cow<str>) -> Result<(), WebviewError> {
         unsafe {
             let c_name = CString::new(&*name).map_err(|_| WebviewError::InvalidArgument)?;
             let err_code = webview_sys::webview_unbind(self.webview_wrapper, c_name.as_ptr());
             let res = WebviewError::from_native(err_code);
-                match res {
-                    Ok(_) => {
-                        self.bindings.remove(&name.to_string()).map(|binding| {
-                            let _ = Box::from_raw(binding[0]);
-                            let _ = Box::from_raw(binding[1]);
-                        });                    
-                    },
-                    _ => {}
-                }
-                res
+            if res.is_ok() {
+                self.bindings.remove(&*name);
+                self.bridge.forget(&name);
+            }
+            res
         }
     }
     fn return_(&mut self, _member: &mut MemberBase, _control: &mut ControlBase, id: Cow<str>, status: i32, result: Cow<str>) -> Result<(), WebviewError> {
+        self.bridge.return_(&id, status, &result)
+    }
+    fn bridge(&self, _member: &MemberBase, _control: &ControlBase) -> WebviewBridge {
+        self.bridge.clone()
+    }
+    fn register_scheme(&mut self, _member: &mut MemberBase, _control: &mut ControlBase, scheme: Cow<str>, handler: SchemeHandler) -> Result<(), WebviewError> {
+        let scheme = crate::api::scheme::check_scheme(&scheme)?;
+        let web_view = self.web_view();
+        let mut schemes = SCHEMES.lock().unwrap();
         unsafe {
-            let c_id = CString::new(&*id).map_err(|_| WebviewError::InvalidArgument)?;
-            let c_result = CString::new(&*result).map_err(|_| WebviewError::InvalidArgument)?;
-            let err_code = webview_sys::webview_return(self.webview_wrapper, c_id.as_ptr(), status, c_result.as_ptr());
-            WebviewError::from_native(err_code)
+            let context = webkit::webkit_web_view_get_context(web_view);
+            if context.is_null() {
+                return Err(WebviewError::InvalidState);
+            }
+            if schemes.registered.insert((context as usize, scheme.clone())) {
+                let c_scheme = CString::new(&*scheme).map_err(|_| WebviewError::InvalidArgument)?;
+                webkit::webkit_web_context_register_uri_scheme(context, c_scheme.as_ptr(), Some(on_scheme_request), ptr::null_mut(), None);
+                let security = webkit::webkit_web_context_get_security_manager(context);
+                webkit::webkit_security_manager_register_uri_scheme_as_secure(security, c_scheme.as_ptr());
+                webkit::webkit_security_manager_register_uri_scheme_as_cors_enabled(security, c_scheme.as_ptr());
+            }
+        }
+        schemes.handlers.insert((web_view as usize, scheme), handler);
+        Ok(())
+    }
+    fn unregister_scheme(&mut self, _member: &mut MemberBase, _control: &mut ControlBase, scheme: Cow<str>) -> Result<(), WebviewError> {
+        let scheme = crate::api::scheme::check_scheme(&scheme)?;
+        let key = (self.web_view() as usize, scheme);
+        SCHEMES.lock().unwrap().handlers.remove(&key).map(|_| ()).ok_or(WebviewError::NotFound)
+    }
+}
+
+impl GtkWebview {
+    fn web_view(&self) -> *mut c_void {
+        unsafe { webview_sys::webview_get_native_handle(self.webview_wrapper, webview_sys::webview_native_handle_kind_t_WEBVIEW_NATIVE_HANDLE_KIND_UI_WIDGET) as *mut c_void }
+    }
+}
+impl Drop for GtkWebview {
+    fn drop(&mut self) {
+        // Clones of the bridge outlive the control, so they must stop reaching the native webview before it goes away.
+        self.bridge.detach();
+        let web_view = self.web_view() as usize;
+        SCHEMES.lock().unwrap().handlers.retain(|(view, _), _| *view != web_view);
+        self.bridge.release_extensions();
+    }
+}
+unsafe extern "C" fn on_scheme_request(request: *mut c_void, _: *mut c_void) {
+    unsafe extern "C" fn collect_header(name: *const ::std::os::raw::c_char, value: *const ::std::os::raw::c_char, headers: *mut c_void) {
+        let headers = &mut *(headers as *mut Vec<(String, String)>);
+        headers.push((CStr::from_ptr(name).to_string_lossy().into_owned(), CStr::from_ptr(value).to_string_lossy().into_owned()));
+    }
+    let web_view = webkit::webkit_uri_scheme_request_get_web_view(request) as usize;
+    let uri = CStr::from_ptr(webkit::webkit_uri_scheme_request_get_uri(request)).to_string_lossy().into_owned();
+    let method = webkit::webkit_uri_scheme_request_get_http_method(request);
+    let method = if method.is_null() { "GET".into() } else { CStr::from_ptr(method).to_string_lossy().into_owned() };
+    let mut scheme_request = SchemeRequest::new(method, uri);
+    let headers = webkit::webkit_uri_scheme_request_get_http_headers(request);
+    if !headers.is_null() {
+        webkit::soup_message_headers_foreach(headers, Some(collect_header), &mut scheme_request.headers as *mut _ as *mut c_void);
+    }
+    let handler = SCHEMES.lock().unwrap().handlers.get(&(web_view, scheme_request.scheme.clone())).cloned();
+    let response = match handler {
+        Some(handler) => panic::catch_unwind(AssertUnwindSafe(|| handler(&scheme_request))).unwrap_or_else(|_| SchemeResponse::new(500, "text/plain", "Internal Server Error")),
+        None => SchemeResponse::not_found(),
+    };
+
+    let bytes = webkit::g_bytes_new(response.body.as_ptr() as *const c_void, response.body.len());
+    let stream = webkit::g_memory_input_stream_new_from_bytes(bytes);
+    webkit::g_bytes_unref(bytes);
+    let native = webkit::webkit_uri_scheme_response_new(stream, response.body.len() as i64);
+    webkit::g_object_unref(stream);
+    webkit::webkit_uri_scheme_response_set_status(native, response.status as c_uint, ptr::null());
+    if let Ok(mime_type) = CString::new(response.mime_type) {
+        webkit::webkit_uri_scheme_response_set_content_type(native, mime_type.as_ptr());
+    }
+    let headers = webkit::soup_message_headers_new(webkit::SOUP_MESSAGE_HEADERS_RESPONSE);
+    for (name, value) in &response.headers {
+        if let (Ok(name), Ok(value)) = (CString::new(&**name), CString::new(&**value)) {
+            webkit::soup_message_headers_append(headers, name.as_ptr(), value.as_ptr());
         }
     }
+    webkit::webkit_uri_scheme_response_set_http_headers(native, headers);
+    webkit::webkit_uri_scheme_request_finish_with_response(request, native);
+    webkit::g_object_unref(native);
 }
 
 impl HasLayoutInner for GtkWebview {
@@ -1,20 +1,68 @@
 use crate::sdk::*;
+use crate::api::document::DataSource;
 
 use plygui_qt::common::{self, *};
 use webview_sys;
 
 use std::str;
 use std::ffi::CStr;
-use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
+use std::collections::{HashMap, HashSet};
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, RwLock};
 
 pub type Webview = AMember<AControl<AWebview<QtWebview>>>;
 
+/// C interface of `mod_qt_scheme.cpp`: the QWebEngineUrlSchemeHandler subclass and direct QWebEnginePage calls.
+mod qt_scheme {
+    use std::os::raw::{c_char, c_int, c_void};
+
+    pub type Callback = unsafe extern "C" fn(job: *mut c_void, profile: *mut c_void, scheme: *const c_char, method: *const c_char, url: *const c_char, headers: *const c_char);
+
+    extern "C" {
+        pub fn plygui_qt_scheme_profile(widget: *mut c_void) -> *mut c_void;
+        pub fn plygui_qt_scheme_install(profile: *mut c_void, scheme: *const c_char, callback: Callback) -> c_int;
+        pub fn plygui_qt_scheme_reply(job: *mut c_void, status: c_int, mime_type: *const c_char, body: *const c_char, length: usize, location: *const c_char);
+        pub fn plygui_qt_set_html(widget: *mut c_void, html: *const c_char, base_url: *const c_char) -> c_int;
+    }
+}
+
+/// QtWebEngine routes scheme requests per profile, not per view, so a scheme of a profile belongs to one view at a time.
+#[derive(Default)]
+struct Schemes {
+    registered: HashSet<(usize, String)>,
+    handlers: HashMap<(usize, String), (usize, SchemeHandler)>,
+}
+
+lazy_static! {
+    static ref SCHEMES: Mutex<Schemes> = Mutex::new(Schemes::default());
+}
+
+struct WebviewBinding {
+    name: String,
+    object: *mut QWidget,
+    context: *mut c_void,
+    callback: *mut c_void,
+    bridge: WebviewBridge,
+    free: unsafe fn(*mut c_void, *mut c_void),
+}
+impl Drop for WebviewBinding {
+    fn drop(&mut self) {
+        unsafe { (self.free)(self.context, self.callback) }
+    }
+}
+unsafe fn free_binding<F, C>(context: *mut c_void, callback: *mut c_void) {
+    let _ = Arc::from_raw(context as *const RwLock<C>);
+    let _ = Box::from_raw(callback as *mut F);
+}
+
 #[repr(C)]
 pub struct QtWebview {
     base: QtControlBase<Webview, QWidget>,
     webview_wrapper: *mut c_void,
-    bindings: HashMap<String, Box<[*mut c_void; 3]>>,
+    bindings: HashMap<String, Box<WebviewBinding>>,
+    bridge: WebviewBridge,
 }
 
 impl<O: crate::Webview> NewWebviewInner<O> for QtWebview {
@@ -26,6 +74,7 @@ impl<O: crate::Webview> NewWebviewInner<O> for QtWebview {
             }, event_handler::<O>),
             webview_wrapper,
             bindings: HashMap::new(),
+            bridge: WebviewBridge::new(webview_wrapper),
         };
         unsafe {
             let ptr = u as *mut _ as u64;
@@ -35,6 +84,15 @@ impl<O: crate::Webview> NewWebviewInner<O> for QtWebview {
         sc
     }
 }
+impl Drop for QtWebview {
+    fn drop(&mut self) {
+        // Clones of the bridge outlive the control, so they must stop reaching the native webview before it goes away.
+        self.bridge.detach();
+        let widget = self.widget() as usize;
+        SCHEMES.lock().unwrap().handlers.retain(|_, (owner, _)| *owner != widget);
+        self.bridge.release_extensions();
+    }
+}
 impl WebviewInner for QtWebview {
     fn new() -> Box<dyn crate::Webview> {        
         let mut b: Box<mem::MaybeUninit<Webview>> = Box::new_uninit();
@@ -54,14 +112,36 @@ impl WebviewInner for QtWebview {
         unsafe {
             let c_url = CString::new(&*url).map_err(|_| WebviewError::InvalidArgument)?;
             let err_code = webview_sys::webview_navigate(self.webview_wrapper, c_url.as_ptr());
-            WebviewError::from_native(err_code)
+            WebviewError::from_native(err_code)?;
         }
+        self.bridge.abandon_calls(None);
+        Ok(())
     }
     fn set_html(&mut self, _member: &mut MemberBase, _control: &mut ControlBase, html: Cow<str>) -> Result<(), WebviewError> {
         unsafe {
             let c_html = CString::new(&*html).map_err(|_| WebviewError::InvalidArgument)?;
             let err_code = webview_sys::webview_set_html(self.webview_wrapper, c_html.as_ptr());
-            WebviewError::from_native(err_code)
+            WebviewError::from_native(err_code)?;
+        }
+        self.bridge.abandon_calls(None);
+        Ok(())
+    }
+    /// QWebEnginePage::setHtml gives the document the origin of `base_url`, but cannot show more than 2 MB of HTML.
+    fn set_html_with_base(&mut self, _member: &mut MemberBase, _control: &mut ControlBase, html: Cow<str>, base_url: Cow<str>) -> Result<(), WebviewError> {
+        let c_html = CString::new(&*html).map_err(|_| WebviewError::InvalidArgument)?;
+        let c_base_url = CString::new(&*base_url).map_err(|_| WebviewError::InvalidArgument)?;
+        match unsafe { qt_scheme::plygui_qt_set_html(self.widget(), c_html.as_ptr(), c_base_url.as_ptr()) } {
+            0 => {}
+            2 => return Err(WebviewError::InvalidArgument),
+            _ => return Err(WebviewError::InvalidState),
+        }
+        self.bridge.abandon_calls(None);
+        Ok(())
+    }
+    fn load_data(&mut self, member: &mut MemberBase, control: &mut ControlBase, data: Cow<[u8]>, mime_type: Cow<str>, encoding: Option<Cow<str>>, base_url: Option<Cow<str>>) -> Result<(), WebviewError> {
+        match crate::api::document::data_source(&self.bridge, &data, &mime_type, encoding.as_deref(), base_url.as_deref())? {
+            DataSource::Html(html) => self.set_html(member, control, Cow::Owned(html)),
+            DataSource::Url(url) => self.navigate(member, control, Cow::Owned(url)),
         }
     }
     fn init(&mut self, _member: &mut MemberBase, _control: &mut ControlBase, js: Cow<str>) -> Result<(), WebviewError> {
@@ -127,14 +207,14 @@ impl WebviewExtInner for QtWebview {
     type W = Webview;
     fn bind<C, F>(&mut self, _member: &mut MemberBase, _control: &mut ControlBase, name: Cow<str>, context: Arc<RwLock<C>>, callback: F) -> Result<(), WebviewError> 
             where F: FnMut(&mut Self::W, &str, &str, &mut C), C: WebviewBindContext {
-        let qwidget = unsafe { self.base.as_qwidget().as_ptr().as_raw_ptr() };
-        let context = Arc::into_raw(context) as *const _ as *mut c_void;
-        let callback = Box::new(callback);
-        let inner_context = Box::new([
-            qwidget as *mut c_void, 
-            context, 
-            Box::into_raw(callback) as *mut c_void
-        ]);
+        let binding = Box::new(WebviewBinding {
+            name: name.to_string(),
+            object: unsafe { self.base.as_qwidget().as_ptr().as_raw_ptr() } as *mut QWidget,
+            context: Arc::into_raw(context) as *const _ as *mut c_void,
+            callback: Box::into_raw(Box::new(callback)) as *mut c_void,
+            bridge: self.bridge.clone(),
+            free: free_binding::<F, C>,
+        });
         extern "C" fn trampoline_webview_bind<
                 F: FnMut(&mut Webview, &str, &str, &mut CC),
                 CC: WebviewBindContext
@@ -144,65 +224,122 @@ impl WebviewExtInner for QtWebview {
                 arg: *mut ::std::os::raw::c_void,
             ) {
                 unsafe {
-                    let arg = &*(arg as *const [*mut c_void; 3]);
-                    let object: *mut QWidget = mem::transmute(arg[0]);
-                    let this: &mut Webview = cast_qobject_mut(&*object).expect("Not a Qt Control");
-                    let id = CStr::from_ptr(id).to_str().expect("id is not a valid string");
-                    let req = CStr::from_ptr(req).to_str().expect("req is not a valid string");
-                    let context: &mut RwLock<CC> = mem::transmute(arg[1]);
-                    let callback: &mut F = mem::transmute(arg[2]);
-                    callback(this, id, req, &mut context.write().unwrap());
-                    mem::forget(object);
+                    let binding = &*(arg as *const WebviewBinding);
+                    crate::api::bridge::dispatch(&binding.bridge, &binding.name, id, req, |id, req| {
+                        let this: &mut Webview = cast_qobject_mut(&*binding.object).expect("Not a Qt Control");
+                        let context = &*(binding.context as *const RwLock<CC>);
+                        let callback = &mut *(binding.callback as *mut F);
+                        crate::api::context::enter(context, |context| callback(this, id, req, context))
+                    });
                 }
             }
-            unsafe {
-                let c_name = CString::new(&*name).map_err(|_| WebviewError::InvalidArgument)?;
-                let err_code = webview_sys::webview_bind(
-                    self.webview_wrapper, 
-                    c_name.as_ptr(), 
-                    Some(trampoline_webview_bind::<F, C>), 
-                    inner_context.as_ptr() as *const _ as *mut c_void
-                );
-                let res = WebviewError::from_native(err_code);
-                match res {
-                    Err(_) => {
-                        self.bindings.remove(&name.to_string()).map(|binding| {
-                            let _ = Arc::from_raw(binding[1]);
-                            let _ = Box::from_raw(binding[2]);
-                        });                    
-                    },
-                    Ok(_) => {
-                        self.bindings.insert(name.to_string(), inner_context);
-                    }
-                }
-                res
+        unsafe {
+            let c_name = CString::new(&*name).map_err(|_| WebviewError::InvalidArgument)?;
+            let err_code = webview_sys::webview_bind(
+                self.webview_wrapper, 
+                c_name.as_ptr(), 
+                Some(trampoline_webview_bind::<F, C>), 
+                &*binding as *const _ as *mut c_void
+            );
+            let res = WebviewError::from_native(err_code);
+            if res.is_ok() {
+                self.bridge.register(&name);
+                self.bindings.insert(name.into_owned(), binding);
             }
+            res
+        }
     }
     fn unbind(&mut self, _member: &mut MemberBase, _control: &mut ControlBase, name: Cow<str>) -> Result<(), WebviewError> {
         unsafe {
             let c_name = CString::new(&*name).map_err(|_| WebviewError::InvalidArgument)?;
             let err_code = webview_sys::webview_unbind(self.webview_wrapper, c_name.as_ptr());
             let res = WebviewError::from_native(err_code);
-                match res {
-                    Ok(_) => {
-                        self.bindings.remove(&name.to_string()).map(|binding| {
-                            let _ = Box::from_raw(binding[0]);
-                            let _ = Box::from_raw(binding[1]);
-                        });                    
-                    },
-                    _ => {}
-                }
-                res
+            if res.is_ok() {
+                self.bindings.remove(&*name);
+                self.bridge.forget(&name);
+            }
+            res
         }
     }
     fn return_(&mut self, _member: &mut MemberBase, _control: &mut ControlBase, id: Cow<str>, status: i32, result: Cow<str>) -> Result<(), WebviewError> {
+        self.bridge.return_(&id, status, &result)
+    }
+    fn bridge(&self, _member: &MemberBase, _control: &ControlBase) -> WebviewBridge {
+        self.bridge.clone()
+    }
+    /// Qt before 6.6 cannot send response headers, and schemes not declared with `QWebEngineUrlScheme::registerScheme`
+    /// before the `QApplication` is created are not treated as secure origins. Fails with `Duplicate` while another view
+    /// of the same profile has a handler for `scheme`.
+    fn register_scheme(&mut self, _member: &mut MemberBase, _control: &mut ControlBase, scheme: Cow<str>, handler: SchemeHandler) -> Result<(), WebviewError> {
+        let scheme = crate::api::scheme::check_scheme(&scheme)?;
+        let widget = self.widget();
+        let mut schemes = SCHEMES.lock().unwrap();
         unsafe {
-            let c_id = CString::new(&*id).map_err(|_| WebviewError::InvalidArgument)?;
-            let c_result = CString::new(&*result).map_err(|_| WebviewError::InvalidArgument)?;
-            let err_code = webview_sys::webview_return(self.webview_wrapper, c_id.as_ptr(), status, c_result.as_ptr());
-            WebviewError::from_native(err_code)
+            let profile = qt_scheme::plygui_qt_scheme_profile(widget);
+            if profile.is_null() {
+                return Err(WebviewError::InvalidState);
+            }
+            let key = (profile as usize, scheme);
+            if schemes.handlers.get(&key).is_some_and(|(owner, _)| *owner != widget as usize) {
+                return Err(WebviewError::Duplicate);
+            }
+            if !schemes.registered.contains(&key) {
+                let c_scheme = CString::new(&*key.1).map_err(|_| WebviewError::InvalidArgument)?;
+                if qt_scheme::plygui_qt_scheme_install(profile, c_scheme.as_ptr(), on_scheme_request) != 0 {
+                    // Someone else's handler already serves the scheme in this profile.
+                    return Err(WebviewError::Duplicate);
+                }
+                schemes.registered.insert(key.clone());
+            }
+            schemes.handlers.insert(key, (widget as usize, handler));
         }
+        Ok(())
     }
+    fn unregister_scheme(&mut self, _member: &mut MemberBase, _control: &mut ControlBase, scheme: Cow<str>) -> Result<(), WebviewError> {
+        let scheme = crate::api::scheme::check_scheme(&scheme)?;
+        let profile = unsafe { qt_scheme::plygui_qt_scheme_profile(self.widget()) };
+        let widget = self.widget() as usize;
+        let mut schemes = SCHEMES.lock().unwrap();
+        let key = (profile as usize, scheme);
+        match schemes.handlers.get(&key) {
+            Some((owner, _)) if *owner == widget => {
+                schemes.handlers.remove(&key);
+                Ok(())
+            }
+            _ => Err(WebviewError::NotFound),
+        }
+    }
+}
+
+impl QtWebview {
+    fn widget(&self) -> *mut c_void {
+        unsafe { self.base.as_qwidget().as_ptr().as_raw_ptr() as *mut c_void }
+    }
+}
+unsafe extern "C" fn on_scheme_request(job: *mut c_void, profile: *mut c_void, scheme: *const c_char, method: *const c_char, url: *const c_char, headers: *const c_char) {
+    let scheme = CStr::from_ptr(scheme).to_string_lossy().into_owned();
+    let mut request = SchemeRequest::new(CStr::from_ptr(method).to_string_lossy().into_owned(), CStr::from_ptr(url).to_string_lossy().into_owned());
+    request.headers = CStr::from_ptr(headers)
+        .to_string_lossy()
+        .split("\r\n")
+        .filter_map(|header| header.split_once(':'))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect();
+    let handler = SCHEMES.lock().unwrap().handlers.get(&(profile as usize, scheme)).map(|(_, handler)| handler.clone());
+    let response = match handler {
+        Some(handler) => panic::catch_unwind(AssertUnwindSafe(|| handler(&request))).unwrap_or_else(|_| SchemeResponse::new(500, "text/plain", "Internal Server Error")),
+        None => SchemeResponse::not_found(),
+    };
+    let mime_type = CString::new(response.mime_type).unwrap_or_default();
+    let location = response.headers.iter().find(|(name, _)| name.eq_ignore_ascii_case("Location")).and_then(|(_, location)| CString::new(&**location).ok());
+    qt_scheme::plygui_qt_scheme_reply(
+        job,
+        response.status as c_int,
+        mime_type.as_ptr(),
+        response.body.as_ptr() as *const c_char,
+        response.body.len(),
+        location.as_ref().map_or(ptr::null(), |location| location.as_ptr()),
+    );
 }
 
 impl HasLayoutInner for QtWebview {
@@ -0,0 +1,111 @@
+// Custom scheme support for the Qt backend. QtWebEngine only takes scheme handlers as C++ subclasses,
+// so this forwards every request to a Rust callback and lets Rust answer it through a plain C interface.
+// It also exposes the few QWebEnginePage calls the C webview library has no equivalent for.
+
+#include <QtGlobal>
+#include <QBuffer>
+#include <QByteArray>
+#include <QString>
+#include <QUrl>
+#include <QWidget>
+#include <QWebEngineView>
+#include <QWebEnginePage>
+#include <QWebEngineProfile>
+#include <QWebEngineUrlRequestJob>
+#include <QWebEngineUrlSchemeHandler>
+
+#include <cstddef>
+
+extern "C" {
+typedef void (*plygui_qt_scheme_callback)(void *job, void *profile, const char *scheme, const char *method, const char *url, const char *headers);
+}
+
+namespace {
+
+class SchemeHandler : public QWebEngineUrlSchemeHandler {
+public:
+    SchemeHandler(const QByteArray &scheme, plygui_qt_scheme_callback callback, QWebEngineProfile *profile)
+        : QWebEngineUrlSchemeHandler(profile), scheme(scheme), callback(callback), profile(profile) {}
+
+    void requestStarted(QWebEngineUrlRequestJob *job) override {
+        QByteArray method = job->requestMethod();
+        QByteArray url = job->requestUrl().toEncoded();
+        QByteArray headers;
+#if QT_VERSION >= QT_VERSION_CHECK(5, 15, 0)
+        const auto requestHeaders = job->requestHeaders();
+        for (auto header = requestHeaders.constBegin(); header != requestHeaders.constEnd(); ++header) {
+            headers += header.key() + ": " + header.value() + "\r\n";
+        }
+#endif
+        callback(job, profile, scheme.constData(), method.constData(), url.constData(), headers.constData());
+    }
+
+private:
+    QByteArray scheme;
+    plygui_qt_scheme_callback callback;
+    QWebEngineProfile *profile;
+};
+
+QWebEngineView *find_view(QWidget *widget) {
+    if (QWebEngineView *view = qobject_cast<QWebEngineView *>(widget)) {
+        return view;
+    }
+    return widget->findChild<QWebEngineView *>();
+}
+
+}
+
+extern "C" {
+
+// Profile of the QWebEngineView that is or is inside `widget`, null if there is none.
+void *plygui_qt_scheme_profile(void *widget) {
+    QWebEngineView *view = widget ? find_view(static_cast<QWidget *>(widget)) : nullptr;
+    return view && view->page() ? view->page()->profile() : nullptr;
+}
+
+// Installs the handler forwarding `scheme` requests made in `profile` to `callback`. Returns 0 on success.
+int plygui_qt_scheme_install(void *profile, const char *scheme, plygui_qt_scheme_callback callback) {
+    QWebEngineProfile *target = static_cast<QWebEngineProfile *>(profile);
+    QByteArray name(scheme);
+    if (target->urlSchemeHandler(name)) {
+        return 1;
+    }
+    target->installUrlSchemeHandler(name, new SchemeHandler(name, callback, target));
+    return target->urlSchemeHandler(name) ? 0 : 1;
+}
+
+// Shows `html` in the view that is or is inside `widget`, resolving relative URLs against `base_url`.
+// Returns 0 on success, 1 without a view and 2 for a malformed `base_url`.
+int plygui_qt_set_html(void *widget, const char *html, const char *base_url) {
+    QWebEngineView *view = widget ? find_view(static_cast<QWidget *>(widget)) : nullptr;
+    if (!view || !view->page()) {
+        return 1;
+    }
+    QUrl base(QString::fromUtf8(base_url));
+    if (!base.isValid()) {
+        return 2;
+    }
+    view->page()->setHtml(QString::fromUtf8(html), base);
+    return 0;
+}
+
+// Answers `job`. Errors map to the closest failure QtWebEngine knows, redirects to `location`.
+void plygui_qt_scheme_reply(void *job, int status, const char *mime_type, const char *body, size_t length, const char *location) {
+    QWebEngineUrlRequestJob *request = static_cast<QWebEngineUrlRequestJob *>(job);
+    if (status >= 300 && status < 400 && location) {
+        request->redirect(QUrl::fromEncoded(QByteArray(location)));
+    } else if (status == 404 || status == 410) {
+        request->fail(QWebEngineUrlRequestJob::UrlNotFound);
+    } else if (status == 401 || status == 403) {
+        request->fail(QWebEngineUrlRequestJob::RequestDenied);
+    } else if (status >= 400) {
+        request->fail(QWebEngineUrlRequestJob::RequestFailed);
+    } else {
+        QBuffer *buffer = new QBuffer(request);
+        buffer->setData(body, static_cast<int>(length));
+        buffer->open(QIODevice::ReadOnly);
+        request->reply(QByteArray(mime_type), buffer);
+    }
+}
+
+}
@@ -1,6 +1,6 @@
 use crate::sdk::*;
+use crate::api::document::DataSource;
 
-use plygui_win32::common::windef::HWND;
 use plygui_win32::common::*;
 use webview_sys;
 
@@ -16,37 +16,35 @@ lazy_static! {
 pub type Webview = AMember<AControl<AWebview<WindowsWebview>>>;
 
 struct WebviewBinding {
-    hwnd: HWND,
-    trampoline: Option<unsafe extern "C" fn(
+    name: String,
+    trampoline: unsafe extern "C" fn(
         id: *const ::std::os::raw::c_char,
         req: *const ::std::os::raw::c_char,
         arg: *mut ::std::os::raw::c_void,
-    )>,
+    ),
     this: *mut c_void,
     context: *mut c_void,
     callback: *mut c_void,
+    bridge: WebviewBridge,
+    free: unsafe fn(*mut c_void, *mut c_void),
 }
-impl Clone for WebviewBinding {
-    fn clone(&self) -> Self {
-        WebviewBinding {
-            hwnd: self.hwnd,
-            trampoline: self.trampoline,
-            context: self.context,
-            callback: self.callback,
-            this: self.this,
-        }
+impl Drop for WebviewBinding {
+    fn drop(&mut self) {
+        unsafe { (self.free)(self.context, self.callback) }
     }
 }
+unsafe fn free_binding<F, C>(context: *mut c_void, callback: *mut c_void) {
+    let _ = Arc::from_raw(context as *const RwLock<C>);
+    let _ = Box::from_raw(callback as *mut F);
+}
 
 #[repr(C)]
 pub struct WindowsWebview {
     base: WindowsControlBase<Webview>,
     webview_wrapper: webview_sys::webview_t,
-    url: Option<String>,
-    html: Option<String>,
-    init_js: Option<String>,
-    eval_js: Option<String>,
+    pending: PendingQueue,
     bindings: HashMap<String, Box<WebviewBinding>>,
+    bridge: WebviewBridge,
 }
 impl<O: crate::Webview> NewWebviewInner<O> for WindowsWebview {
     fn with_uninit(_: &mut mem::MaybeUninit<O>) -> Self {
@@ -54,13 +52,16 @@ impl<O: crate::Webview> NewWebviewInner<O> for WindowsWebview {
             base: WindowsControlBase::with_handler(Some(handler::<O>)),
             webview_wrapper: ptr::null_mut(),
             bindings: HashMap::new(),
-            url: None,
-            html: None,
-            init_js: None,
-            eval_js: None,
+            bridge: WebviewBridge::new(ptr::null_mut()),
+            pending: PendingQueue::new(),
         }
     }
 }
+impl Drop for WindowsWebview {
+    fn drop(&mut self) {
+        self.bridge.release_extensions();
+    }
+}
 impl WebviewInner for WindowsWebview {
     fn new() -> Box<dyn crate::Webview> {        
         let mut b: Box<mem::MaybeUninit<Webview>> = Box::new_uninit();
@@ -77,52 +78,35 @@ impl WebviewInner for WindowsWebview {
         }
     }
     fn navigate(&mut self, _member: &mut MemberBase, _control: &mut ControlBase, url: Cow<str>) -> Result<(), WebviewError> {
-        if !self.base.hwnd.is_null() {
-            unsafe {
-                let c_url = CString::new(&*url).map_err(|_| WebviewError::InvalidArgument)?;
-                let err_code = webview_sys::webview_navigate(self.webview_wrapper, c_url.as_ptr());
-                WebviewError::from_native(err_code)
-            }
-        } else {
-            self.url = Some(url.into_owned());
-            Ok(())
-        }
+        self.enqueue(PendingOperation::Navigate(url.into_owned()))?;
+        self.bridge.abandon_calls(None);
+        Ok(())
     }
     fn set_html(&mut self, _member: &mut MemberBase, _control: &mut ControlBase, html: Cow<str>) -> Result<(), WebviewError> {
-        if !self.base.hwnd.is_null() {
-            unsafe {
-                let c_html = CString::new(&*html).map_err(|_| WebviewError::InvalidArgument)?;
-                let err_code = webview_sys::webview_set_html(self.webview_wrapper, c_html.as_ptr());
-                WebviewError::from_native(err_code)
-            }
-        } else {
-            self.html = Some(html.into_owned());
-            Ok(())
+        self.enqueue(PendingOperation::SetHtml(html.into_owned()))?;
+        self.bridge.abandon_calls(None);
+        Ok(())
+    }
+    /// The base goes in as an injected `<base>`, see `check_base` for why `file://` bases are refused.
+    fn set_html_with_base(&mut self, member: &mut MemberBase, control: &mut ControlBase, html: Cow<str>, base_url: Cow<str>) -> Result<(), WebviewError> {
+        check_base(&base_url)?;
+        let html = crate::api::document::inject_base(&html, &base_url);
+        self.set_html(member, control, Cow::Owned(html))
+    }
+    fn load_data(&mut self, member: &mut MemberBase, control: &mut ControlBase, data: Cow<[u8]>, mime_type: Cow<str>, encoding: Option<Cow<str>>, base_url: Option<Cow<str>>) -> Result<(), WebviewError> {
+        if let Some(ref base_url) = base_url {
+            check_base(base_url)?;
+        }
+        match crate::api::document::data_source(&self.bridge, &data, &mime_type, encoding.as_deref(), base_url.as_deref())? {
+            DataSource::Html(html) => self.set_html(member, control, Cow::Owned(html)),
+            DataSource::Url(url) => self.navigate(member, control, Cow::Owned(url)),
         }
     }
     fn init(&mut self, _member: &mut MemberBase, _control: &mut ControlBase, js: Cow<str>) -> Result<(), WebviewError> {
-        if !self.base.hwnd.is_null() {
-            unsafe {
-                let c_js = CString::new(&*js).map_err(|_| WebviewError::InvalidArgument)?;
-                let err_code = webview_sys::webview_init(self.webview_wrapper, c_js.as_ptr());
-                WebviewError::from_native(err_code)
-            }
-        } else {
-            self.init_js = Some(js.into_owned());
-            Ok(())
-        }
+        self.enqueue(PendingOperation::Init(js.into_owned()))
     }
     fn eval(&mut self, _member: &mut MemberBase, _control: &mut ControlBase, js: Cow<str>) -> Result<(), WebviewError> {
-        if !self.base.hwnd.is_null() {
-            unsafe {
-                let c_js = CString::new(&*js).map_err(|_| WebviewError::InvalidArgument)?;
-                let err_code = webview_sys::webview_eval(self.webview_wrapper, c_js.as_ptr());
-                WebviewError::from_native(err_code)
-            }
-        } else {
-            self.eval_js = Some(js.into_owned());
-            Ok(())
-        }
+        self.enqueue(PendingOperation::Eval(js.into_owned()))
     }    
     fn url(&'_ self, _member: &MemberBase, _control: &ControlBase) -> Result<Cow<'_, str>,WebviewError> {
         if !self.base.hwnd.is_null() {
@@ -135,10 +119,7 @@ impl WebviewInner for WindowsWebview {
                 Ok(Cow::Owned(url.to_string()))
             }
         } else {
-            match &self.url {
-                Some(u) => Ok(Cow::Owned(u.clone())),
-                None => Err(WebviewError::NotFound),
-            }
+            self.pending.url().map(|url| Cow::Owned(url.to_string())).ok_or(WebviewError::NotFound)
         }
     }    
     fn title(&'_ self, _member: &MemberBase, _control: &ControlBase) -> Result<Cow<'_, str>,WebviewError> {
@@ -198,81 +179,130 @@ impl WebviewInner for WindowsWebview {
 
 }
 impl WindowsWebview {
+    fn enqueue(&mut self, operation: PendingOperation) -> Result<(), WebviewError> {
+        if self.webview_wrapper.is_null() {
+            return self.pending.push(operation);
+        }
+        let res = self.apply(&operation);
+        if res.is_ok() {
+            self.pending.remember(operation);
+        }
+        res
+    }
+    fn apply(&mut self, operation: &PendingOperation) -> Result<(), WebviewError> {
+        if let PendingOperation::Bind(name) = operation {
+            return self.bind_inner(Cow::Borrowed(name));
+        }
+        unsafe {
+            let err_code = match operation {
+                PendingOperation::Navigate(url) => {
+                    let c_url = CString::new(&**url).map_err(|_| WebviewError::InvalidArgument)?;
+                    webview_sys::webview_navigate(self.webview_wrapper, c_url.as_ptr())
+                }
+                PendingOperation::SetHtml(html) => {
+                    let c_html = CString::new(&**html).map_err(|_| WebviewError::InvalidArgument)?;
+                    webview_sys::webview_set_html(self.webview_wrapper, c_html.as_ptr())
+                }
+                PendingOperation::Init(js) => {
+                    let c_js = CString::new(&**js).map_err(|_| WebviewError::InvalidArgument)?;
+                    webview_sys::webview_init(self.webview_wrapper, c_js.as_ptr())
+                }
+                PendingOperation::Eval(js) => {
+                    let c_js = CString::new(&**js).map_err(|_| WebviewError::InvalidArgument)?;
+                    webview_sys::webview_eval(self.webview_wrapper, c_js.as_ptr())
+                }
+                PendingOperation::Bind(_) => unreachable!(),
+            };
+            WebviewError::from_native(err_code)
+        }
+    }
     fn bind_inner(&mut self, name: Cow<str>) -> Result<(), WebviewError> {
-        let binding = self.bindings.get_mut(&name.to_string()).ok_or(WebviewError::NotFound)?;
-        let inner_context = Box::into_raw(binding.clone());
+        let binding = self.bindings.get(&*name).ok_or(WebviewError::NotFound)?;
         unsafe {
             let c_name = CString::new(&*name).map_err(|_| WebviewError::InvalidArgument)?;
-            let f = binding.trampoline.clone();
             let err_code = webview_sys::webview_bind(
                 self.webview_wrapper, 
                 c_name.as_ptr(), 
-                f, 
-                inner_context as *mut ::std::ffi::c_void
+                Some(binding.trampoline), 
+                &**binding as *const _ as *mut ::std::ffi::c_void
             );
             let res = WebviewError::from_native(err_code);
-            match res {
-                Err(_) => {
-                    self.bindings.remove(&name.to_string()).map(|binding| {
-                        let _ = Arc::from_raw(binding.context);
-                        let _ = Box::from_raw(binding.callback);
-                    });                    
-                },
-                _ => {
-                    binding.trampoline = None;
-                }
+            if res.is_err() {
+                self.bindings.remove(&*name);
+                self.pending.forget_bind(&name);
+                self.bridge.forget(&name);
             }
             res
         }
     }
 }
+/// WebView2 does not let documents set from a string load `file://` resources, so such a base would only break every
+/// relative URL. Serve the files through `serve_host_or_loopback` instead.
+fn check_base(base_url: &str) -> Result<(), WebviewError> {
+    if base_url.get(..5).is_some_and(|scheme| scheme.eq_ignore_ascii_case("file:")) {
+        return Err(WebviewError::InvalidArgument);
+    }
+    Ok(())
+}
+
 impl WebviewExtInner for WindowsWebview {
     type W = Webview;
     fn bind<C, F>(&mut self, member: &mut MemberBase, _control: &mut ControlBase, name: Cow<str>, context: Arc<RwLock<C>>, callback: F) -> Result<(), WebviewError> 
             where F: FnMut(&mut Self::W, &str, &str, &mut C), C: WebviewBindContext {
-        let context = Arc::into_raw(context) as *const _ as *mut c_void;
-        let callback = Box::new(callback);
-        let inner_context = Box::new(WebviewBinding { 
-            hwnd: self.base.hwnd, 
-            trampoline: Some(trampoline_webview_bind::<F, C>), 
-            context: context, 
-            callback: Box::into_raw(callback) as *mut c_void,
+        if self.bindings.contains_key(&*name) {
+            return Err(WebviewError::Duplicate);
+        }
+        let binding = Box::new(WebviewBinding { 
+            name: name.to_string(),
+            trampoline: trampoline_webview_bind::<F, C>, 
             this: member as *mut _ as *mut c_void,
+            context: Arc::into_raw(context) as *const _ as *mut c_void, 
+            callback: Box::into_raw(Box::new(callback)) as *mut c_void,
+            bridge: self.bridge.clone(),
+            free: free_binding::<F, C>,
         });
-        self.bindings.insert(name.to_string(), inner_context);
-        if !self.base.hwnd.is_null() {
-            self.bind_inner(name)
-        } else {
-            Ok(())
+        self.bindings.insert(name.to_string(), binding);
+        self.bridge.register(&name);
+        let res = self.enqueue(PendingOperation::Bind(name.to_string()));
+        if res.is_err() {
+            self.bindings.remove(&*name);
+            self.bridge.forget(&name);
         }
+        res
     }
     fn unbind(&mut self, _member: &mut MemberBase, _control: &mut ControlBase, name: Cow<str>) -> Result<(), WebviewError> {
-        unsafe {
-            self.bindings.remove(&name.to_string()).map(|binding| {
-                let _ = Arc::from_raw(binding.context);
-                let _ = Box::from_raw(binding.callback);
-            });  
-            if !self.base.hwnd.is_null() {
+        let res = if !self.base.hwnd.is_null() {
+            unsafe {
                 let c_name = CString::new(&*name).map_err(|_| WebviewError::InvalidArgument)?;
                 let err_code = webview_sys::webview_unbind(self.webview_wrapper, c_name.as_ptr());
                 WebviewError::from_native(err_code)
-            } else {
-                Ok(())
             }
-        }
+        } else {
+            Ok(())
+        };
+        self.bindings.remove(&*name);
+        self.pending.forget_bind(&name);
+        self.bridge.forget(&name);
+        res
     }
     fn return_(&mut self, _member: &mut MemberBase, _control: &mut ControlBase, id: Cow<str>, status: i32, result: Cow<str>) -> Result<(), WebviewError> {
         if !self.base.hwnd.is_null() {
-            unsafe {
-               let c_id = CString::new(&*id).map_err(|_| WebviewError::InvalidArgument)?;
-               let c_result = CString::new(&*result).map_err(|_| WebviewError::InvalidArgument)?;
-               let err_code = webview_sys::webview_return(self.webview_wrapper, c_id.as_ptr(), status, c_result.as_ptr());
-               WebviewError::from_native(err_code)
-            }
+            self.bridge.return_(&id, status, &result)
         } else {
             Err(WebviewError::InvalidState)
         }
     }
+    fn bridge(&self, _member: &MemberBase, _control: &ControlBase) -> WebviewBridge {
+        self.bridge.clone()
+    }
+    fn register_scheme(&mut self, _member: &mut MemberBase, _control: &mut ControlBase, scheme: Cow<str>, _handler: SchemeHandler) -> Result<(), WebviewError> {
+        crate::api::scheme::check_scheme(&scheme)?;
+        // WebView2 needs custom schemes declared in its environment options at creation, which webview_sys does not expose.
+        Err(WebviewError::MissingDependency)
+    }
+    fn unregister_scheme(&mut self, _member: &mut MemberBase, _control: &mut ControlBase, _scheme: Cow<str>) -> Result<(), WebviewError> {
+        Err(WebviewError::MissingDependency)
+    }
 }
 impl Spawnable for WindowsWebview {
     fn spawn() -> Box<dyn controls::Control> {
@@ -293,37 +323,23 @@ impl ControlInner for WindowsWebview {
                 winuser::SetWindowPos(hwnd, ptr::null_mut(), x, y, w as i32, h as i32, 0);
                 self.base.hwnd = hwnd;
                 self.webview_wrapper = webview;
+                self.bridge.attach(webview);
+            }
+            for operation in self.pending.replay() {
+                let _ = self.apply(&operation);
             }
         } else {
             unsafe {
                 winuser::SetParent(self.base.hwnd, parent.native_id() as windef::HWND);
             }
         }
-        if self.url.is_some() {
-            let url = self.url.to_owned();
-            let _ = self.navigate(member, control, Cow::Owned(url.unwrap()));
-        }
-        if self.html.is_some() {
-            let html = self.html.to_owned();
-            let _ = self.set_html(member, control, Cow::Owned(html.unwrap()));
-        }
-        if self.init_js.is_some() {
-            let init_js = self.init_js.to_owned();
-            let _ = self.init(member, control, Cow::Owned(init_js.unwrap()));
-        }
-        if self.eval_js.is_some() {
-            let eval_js = self.eval_js.to_owned();
-            let _ = self.eval(member, control, Cow::Owned(eval_js.unwrap()));
-        }
-        let keys = self.bindings.keys().cloned().collect::<Vec<_>>();
-        for name in keys {
-            let _ = self.bind_inner(Cow::Borrowed(&*name));
-        }
     }
     fn on_removed_from_container(&mut self, _member: &mut MemberBase, _control: &mut ControlBase, _: &dyn controls::Container) {
+        self.bridge.detach();
         unsafe {
             webview_sys::webview_destroy(self.webview_wrapper);
         }
+        self.webview_wrapper = ptr::null_mut();
         self.base.hwnd = 0 as windef::HWND;
         self.base.subclass_id = 0;
     }
@@ -455,11 +471,11 @@ unsafe extern "C" fn trampoline_webview_bind<
     req: *const ::std::os::raw::c_char,
     arg: *mut ::std::os::raw::c_void,
 ) {
-    let arg = &*(arg as *const WebviewBinding);
-    let this: &mut Webview = mem::transmute(arg.this);
-    let id = CStr::from_ptr(id).to_str().expect("id is not a valid string");
-    let req = CStr::from_ptr(req).to_str().expect("req is not a valid string");
-    let context: &mut RwLock<CC> = mem::transmute(arg.context);
-    let callback: &mut F = mem::transmute(arg.callback);
-    callback(this, id, req, &mut context.write().unwrap());
-}
\ No newline at end of file
+    let binding = &*(arg as *const WebviewBinding);
+    crate::api::bridge::dispatch(&binding.bridge, &binding.name, id, req, |id, req| {
+        let this = &mut *(binding.this as *mut Webview);
+        let context = &*(binding.context as *const RwLock<CC>);
+        let callback = &mut *(binding.callback as *mut F);
+        crate::api::context::enter(context, |context| callback(this, id, req, context))
+    });
+}
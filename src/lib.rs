@@ -8,6 +8,8 @@ extern crate plygui_api;
 extern crate plygui_macros;
 
 extern crate webview_sys;
+extern crate serde;
+extern crate serde_json;
 
 #[cfg(all(target_os = "macos", feature = "cocoa_"))]
 #[macro_use]
@@ -30,4 +32,19 @@ pub mod sdk;
 pub mod imp;
 pub mod api;
 
-pub use crate::api::webview::{Webview, NewWebview, WebviewExt, WebviewBindContext};
\ No newline at end of file
+pub use crate::api::webview::{Webview, NewWebview, WebviewExt, WebviewBindContext};
+pub use crate::api::bytes::Bytes;
+pub use crate::api::bridge::{WebviewBridge, Responder, BindPanic, set_panic_hook, clear_panic_hook};
+pub use crate::api::middleware::{BindCall, BindMiddleware};
+pub use crate::api::origin::{Caller, OriginPattern};
+pub use crate::api::registry::BindingInfo;
+pub use crate::api::namespace::{WebviewApi, WebviewNamespace};
+pub use crate::api::typescript::{TsType, TsArgs, TsSignature};
+pub use crate::api::eval::{JsError, JsFuture};
+pub use crate::api::rpc::{JsonRpc, RpcError};
+pub use crate::api::scheme::{SchemeHandler, SchemeRequest, SchemeResponse};
+pub use crate::api::assets::{AssetProvider, AssetServer, DirectoryAssets, EmbeddedAssets};
+pub use crate::api::loopback::LoopbackServer;
+pub use crate::api::store::{SharedStore, StoreChange};
+pub use crate::api::stream::StreamSink;
+pub use crate::api::typed::{BindError, IntoBindError};
\ No newline at end of file
@@ -1,3 +1,6 @@
 pub use super::api::webview::{WebviewInner, AWebview, NewWebviewInner, WebviewError, WebviewExtInner, WebviewBindContext};
+pub use super::api::bridge::WebviewBridge;
+pub use super::api::pending::{PendingOperation, PendingQueue};
+pub use super::api::scheme::{SchemeHandler, SchemeRequest, SchemeResponse};
 
 pub use crate::imp::webview::WebviewControl;